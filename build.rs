@@ -1,40 +1,202 @@
+use zip::write::FileOptions;
 use zip::ZipWriter;
 
+use ron::de::from_reader;
+use ron::ser::{to_string_pretty, PrettyConfig};
+
 use std::fs::{read_dir, File};
 use std::io::{Read, Result as IOResult, Write};
 use std::path::Path;
 
+#[path = "src/engine/pack.rs"]
+#[allow(dead_code)]
+mod pack;
+
+#[path = "src/engine/cook.rs"]
+#[allow(dead_code)]
+mod cook;
+
+use cook::{cooked_path, glyphs_ext, CookedFont, CookedImage, CookedMesh, IMAGE_EXT, MESH_EXT};
+use pack::{AssetKind, Manifest, ManifestEntry, PackPolicy, MANIFEST_FILE, POLICY_FILE};
+
+const ASSETS_DIR: &str = "assets/";
+
 fn main() {
-	if cfg!(feature = "pack") {
+	if cfg!(feature = "zip_assets") {
 		zip_assets();
 	}
 }
 
 fn zip_assets() {
 	let mut zip_writer = ZipWriter::new(File::create("assets.zip").unwrap());
-	zip_dir(&mut zip_writer, "assets/").expect("Error creating zip for assets");
+	let mut manifest = Manifest::default();
+	let policy = load_policy();
+	zip_dir(&mut zip_writer, &mut manifest, &policy, ASSETS_DIR)
+		.expect("Error creating zip for assets");
+
+	// The manifest goes last, once every file has been hashed
+	let manifest_str =
+		to_string_pretty(&manifest, PrettyConfig::default()).expect("Error serializing manifest");
+	zip_writer
+		.start_file_from_path(&Path::new(ASSETS_DIR).join(MANIFEST_FILE), options())
+		.expect("Error starting manifest in zip");
+	zip_writer
+		.write_all(manifest_str.as_bytes())
+		.expect("Error writing manifest to zip");
 }
 
-fn zip_dir<P: AsRef<Path>>(zip_writer: &mut ZipWriter<File>, dir_ref: P) -> IOResult<()> {
+// The packing policy is optional, without it everything is deflated
+// Without the build time, so building the same assets twice gives the same pack
+fn options() -> FileOptions {
+	FileOptions::default().last_modified_time(zip::DateTime::default())
+}
+
+fn load_policy() -> PackPolicy {
+	if let Ok(f) = File::open(POLICY_FILE) {
+		from_reader(f).expect("Error parsing packing policy")
+	} else {
+		PackPolicy::default()
+	}
+}
+
+fn zip_dir<P: AsRef<Path>>(
+	zip_writer: &mut ZipWriter<File>,
+	manifest: &mut Manifest,
+	policy: &PackPolicy,
+	dir_ref: P,
+) -> IOResult<()> {
 	let dir: &Path = dir_ref.as_ref();
 	if dir.is_dir() {
 		zip_writer
-			.add_directory_from_path(dir.as_ref(), Default::default())
+			.add_directory_from_path(dir.as_ref(), options())
 			.expect("Error adding dir to zip");
-		for entry in read_dir(dir)? {
-			let entry = entry?;
-			let path = entry.path();
+		// Sorted, so the same assets always give the same pack
+		let mut paths = read_dir(dir)?
+			.map(|e| e.map(|e| e.path()))
+			.collect::<IOResult<Vec<_>>>()?;
+		paths.sort();
+		for path in paths {
 			if path.is_dir() {
-				zip_dir(zip_writer, &path)?;
+				zip_dir(zip_writer, manifest, policy, &path)?;
 			} else {
-				zip_writer
-					.start_file_from_path(&path, Default::default())
-					.expect("Error starting file to zip");
+				let asset_path = asset_path(&path);
+				if asset_path == MANIFEST_FILE {
+					// It would be overwritten by the generated one
+					continue;
+				}
 				let mut buf = Vec::new();
 				File::open(&path)?.read_to_end(&mut buf)?;
-				zip_writer.write_all(&mut buf)?;
+				let (cooked, keep_source) = cook_asset(policy, &path, &asset_path, &buf);
+				if keep_source {
+					zip_file(zip_writer, manifest, policy, asset_path, &buf)?;
+				}
+				for (cooked_path, data) in cooked {
+					zip_file(zip_writer, manifest, policy, cooked_path, &data)?;
+				}
 			}
 		}
 	}
 	Ok(())
 }
+
+fn zip_file(
+	zip_writer: &mut ZipWriter<File>,
+	manifest: &mut Manifest,
+	policy: &PackPolicy,
+	asset_path: String,
+	data: &[u8],
+) -> IOResult<()> {
+	let compression = policy.compression_for(&asset_path);
+	zip_writer
+		.start_file_from_path(
+			&Path::new(ASSETS_DIR).join(&asset_path),
+			options().compression_method(compression.into()),
+		)
+		.expect("Error starting file to zip");
+	zip_writer.write_all(data)?;
+	manifest.add(ManifestEntry::new(asset_path, data, compression));
+	Ok(())
+}
+
+// Cooks an asset, returning the cooked files and whether the source has to be packed too.
+// Meshes and images are replaced by their cooked form, fonts are kept for the sizes without an atlas.
+// If an asset can't be cooked it's packed as it is, and the runtime parses it like a loose file
+fn cook_asset(
+	policy: &PackPolicy,
+	path: &Path,
+	asset_path: &str,
+	data: &[u8],
+) -> (Vec<(String, Vec<u8>)>, bool) {
+	let ext = path
+		.extension()
+		.and_then(|e| e.to_str())
+		.unwrap_or("")
+		.to_lowercase();
+	let (cooked, keep_source) = match AssetKind::from_path(path) {
+		AssetKind::Model => (
+			cook_mesh(path, data).map(|d| vec![(cooked_path(asset_path, MESH_EXT), d)]),
+			false,
+		),
+		// HDR images would lose their range as RGBA8
+		AssetKind::Image if ext != "hdr" => match CookedImage::new(data, path) {
+			Ok(Some(img)) => (
+				cook::encode(&img).map(|d| vec![(cooked_path(asset_path, IMAGE_EXT), d)]),
+				false,
+			),
+			Ok(None) => (Ok(Vec::new()), true),
+			Err(e) => (Err(e), true),
+		},
+		AssetKind::Font => (
+			policy
+				.font_sizes
+				.iter()
+				.map(|&size| {
+					CookedFont::new(data.to_vec(), size)
+						.and_then(|font| cook::encode(&font))
+						.map(|d| (cooked_path(asset_path, &glyphs_ext(size)), d))
+				})
+				.collect(),
+			true,
+		),
+		_ => (Ok(Vec::new()), true),
+	};
+	match cooked {
+		Ok(cooked) => (cooked, keep_source),
+		Err(e) => {
+			println!(
+				"cargo:warning=Can't cook {}, packing it as it is: {}",
+				asset_path, e
+			);
+			(Vec::new(), true)
+		}
+	}
+}
+
+fn cook_mesh(path: &Path, data: &[u8]) -> Result<Vec<u8>, String> {
+	let obj = String::from_utf8(data.to_vec()).map_err(|e| e.to_string())?;
+	let mtl = match cook::mtl_lib(&obj) {
+		Some((_, mtl_lib)) => {
+			let mtl_path = cook::mtl_path(path, &mtl_lib);
+			let mut content = String::new();
+			File::open(&mtl_path)
+				.and_then(|mut f| f.read_to_string(&mut content))
+				.map_err(|e| format!("{:?}: {}", mtl_path, e))?;
+			Some(content)
+		}
+		None => None,
+	};
+	let mesh = CookedMesh::from_obj(obj, mtl).map_err(|e| match e.line {
+		Some(line) => format!("line {}: {}", line, e.msg),
+		None => e.msg,
+	})?;
+	cook::encode(&mesh)
+}
+
+// The path of a file relative to the assets dir, `/` separated
+fn asset_path(p: &Path) -> String {
+	let mut r = Vec::<String>::new();
+	for s in p.strip_prefix(ASSETS_DIR).unwrap_or(p).iter() {
+		r.push(s.to_str().unwrap().to_string());
+	}
+	r.join("/")
+}
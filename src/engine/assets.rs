@@ -0,0 +1,215 @@
+use luminance::context::GraphicsContext;
+use luminance::pixel::NormRGBA8UI;
+use luminance::texture::{Dim2, Texture};
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::error::{Error, Result};
+use super::texture::{AnyTexture, TextureSettings};
+use super::utils::{
+	load_texture, load_with_settings, placeholder_image, read_image, read_texture_image,
+};
+use super::FileLoader;
+
+/// Cheap shared reference to an asset. Cloning it only increases the reference count
+pub struct Handle<T>(Rc<T>);
+
+#[allow(dead_code)]
+impl<T> Handle<T> {
+	/// Wraps an asset that isn't cached, like a generated texture
+	pub fn new(asset: T) -> Self {
+		Self(Rc::new(asset))
+	}
+
+	pub fn ref_count(&self) -> usize {
+		Rc::strong_count(&self.0)
+	}
+
+	/// Whether both handles point to the same asset
+	pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+		Rc::ptr_eq(&a.0, &b.0)
+	}
+}
+
+impl<T> Clone for Handle<T> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone())
+	}
+}
+
+impl<T> Deref for Handle<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.0
+	}
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Handle<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		write!(f, "Handle({:?})", self.0)
+	}
+}
+
+/// An asset that can be decoded from a file. The path is relative to the assets root
+pub trait Asset: Sized + 'static {
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self>;
+}
+
+impl Asset for image::RgbaImage {
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self> {
+		read_image(file_loader, path)
+	}
+}
+
+pub type Texture2D = Texture<Dim2, NormRGBA8UI>;
+
+/// Contents of an `AssetServer`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AssetStats {
+	pub assets: usize, // Decoded assets, like models and fonts
+	pub textures: usize,
+	pub texture_bytes: usize, // Base levels of the textures, without mipmaps
+	pub unused: usize,        // Only held by the cache, `collect_garbage` would drop them
+}
+
+/// Cache of decoded assets, by path and type, so every asset is only loaded once.
+/// Textures are also cached by their settings, the same image can be used with several samplers
+pub struct AssetServer {
+	assets: HashMap<(PathBuf, TypeId), Rc<dyn Any>>,
+	textures: HashMap<(PathBuf, TextureSettings), Rc<AnyTexture>>,
+}
+
+#[allow(dead_code)]
+impl AssetServer {
+	pub fn new() -> Self {
+		Self {
+			assets: HashMap::new(),
+			textures: HashMap::new(),
+		}
+	}
+
+	/// Gets an asset from the cache, loading it if it isn't there
+	pub fn load<A: Asset>(
+		&mut self,
+		file_loader: &mut FileLoader,
+		path: &Path,
+	) -> Result<Handle<A>> {
+		if let Some(handle) = self.get(path) {
+			return Ok(handle);
+		}
+		let asset = A::load(file_loader, path)?;
+		Ok(self.insert(path, asset))
+	}
+
+	/// Gets a texture with the default settings from the cache, decoding and uploading the image if it isn't there
+	pub fn texture<C: GraphicsContext>(
+		&mut self,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		path: &Path,
+	) -> Result<Handle<AnyTexture>> {
+		self.texture_with_settings(file_loader, surface, path, &TextureSettings::default())
+	}
+
+	pub fn texture_with_settings<C: GraphicsContext>(
+		&mut self,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		path: &Path,
+		settings: &TextureSettings,
+	) -> Result<Handle<AnyTexture>> {
+		let key = (path.to_path_buf(), *settings);
+		if let Some(rc) = self.textures.get(&key) {
+			return Ok(Handle(rc.clone()));
+		}
+		if settings.anisotropy.is_some() {
+			return Err(Error::invalid(
+				path,
+				"anisotropic filtering isn't supported",
+			));
+		}
+		let img = read_texture_image(file_loader, path, settings.format)?;
+		let rc = Rc::new(load_texture(surface, img, settings)?);
+		self.textures.insert(key, rc.clone());
+		Ok(Handle(rc))
+	}
+
+	/// Like `texture_with_settings`, but reports the error and uses a placeholder texture if it can't be loaded.
+	/// The placeholder is cached as the texture, so the error is only reported once
+	pub fn texture_or_placeholder<C: GraphicsContext>(
+		&mut self,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		path: &Path,
+		settings: &TextureSettings,
+	) -> Result<Handle<AnyTexture>> {
+		match self.texture_with_settings(file_loader, surface, path, settings) {
+			Ok(handle) => Ok(handle),
+			Err(e) => {
+				eprintln!("{}, using a placeholder", e);
+				let tex = load_with_settings(surface, placeholder_image(), settings)?;
+				let rc = Rc::new(AnyTexture::from(tex));
+				self.textures
+					.insert((path.to_path_buf(), *settings), rc.clone());
+				Ok(Handle(rc))
+			}
+		}
+	}
+
+	/// Adds an asset to the cache, replacing the one of the same type at that path
+	pub fn insert<A: 'static>(&mut self, path: &Path, asset: A) -> Handle<A> {
+		let rc = Rc::new(asset);
+		self.assets
+			.insert((path.to_path_buf(), TypeId::of::<A>()), rc.clone());
+		Handle(rc)
+	}
+
+	pub fn get<A: 'static>(&self, path: &Path) -> Option<Handle<A>> {
+		self.assets
+			.get(&(path.to_path_buf(), TypeId::of::<A>()))
+			.and_then(|rc| rc.clone().downcast::<A>().ok())
+			.map(Handle)
+	}
+
+	/// Removes every asset loaded from `path`, so the next load reads the file again.
+	/// Handles already given out keep the old asset
+	pub fn invalidate(&mut self, path: &Path) {
+		self.assets.retain(|(p, _), _| p != path);
+		self.textures.retain(|(p, _), _| p != path);
+	}
+
+	/// Drops the assets that aren't used anywhere else, returns how many were dropped
+	pub fn collect_garbage(&mut self) -> usize {
+		let len = self.len();
+		self.assets.retain(|_, rc| Rc::strong_count(rc) > 1);
+		self.textures.retain(|_, rc| Rc::strong_count(rc) > 1);
+		len - self.len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.assets.len() + self.textures.len()
+	}
+
+	pub fn stats(&self) -> AssetStats {
+		let unused = self
+			.assets
+			.values()
+			.filter(|rc| Rc::strong_count(rc) == 1)
+			.count() + self
+			.textures
+			.values()
+			.filter(|rc| Rc::strong_count(rc) == 1)
+			.count();
+		AssetStats {
+			assets: self.assets.len(),
+			textures: self.textures.len(),
+			texture_bytes: self.textures.values().map(|t| t.bytes()).sum(),
+			unused,
+		}
+	}
+}
@@ -0,0 +1,127 @@
+use ron::ser::{to_string_pretty, PrettyConfig};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::error::{Error, Result};
+use super::texture::{TextureData, TextureSettings};
+
+// Biggest atlas the packer makes, most GPUs support it
+const MAX_SIZE: u32 = 8192;
+
+/// A packed atlas image, with one UV state per sprite named like the sprite
+pub struct Atlas {
+	pub image: image::RgbaImage,
+	pub data: TextureData,
+}
+
+#[allow(dead_code)]
+impl Atlas {
+	/// Packs the sprites in rows (shelves), the tallest first. `file` is the name the atlas image will have
+	/// in the textures dir, and `padding` the empty pixels between sprites, to avoid bleeding when filtering
+	pub fn pack<T: ToString>(
+		sprites: Vec<(String, image::RgbaImage)>,
+		file: T,
+		padding: u32,
+	) -> Result<Self> {
+		let file = file.to_string();
+		if sprites.is_empty() {
+			return Err(Error::invalid(&file, "no sprites to pack"));
+		}
+		let mut sprites = sprites;
+		sprites.sort_by(|(a_name, a), (b_name, b)| {
+			b.height().cmp(&a.height()).then(a_name.cmp(b_name))
+		});
+
+		// Start with a square that could hold all of them, the height is whatever the rows need
+		let area: u64 = sprites
+			.iter()
+			.map(|(_, s)| (s.width() + padding) as u64 * (s.height() + padding) as u64)
+			.sum();
+		let widest = sprites.iter().map(|(_, s)| s.width()).max().unwrap();
+		let width = ((area as f64).sqrt().ceil() as u32)
+			.max(widest)
+			.next_power_of_two();
+
+		let mut positions = Vec::new();
+		let (mut x, mut y, mut row_height) = (0, 0, 0);
+		for (_, sprite) in &sprites {
+			if x + sprite.width() > width {
+				x = 0;
+				y += row_height + padding;
+				row_height = 0;
+			}
+			positions.push((x, y));
+			x += sprite.width() + padding;
+			row_height = row_height.max(sprite.height());
+		}
+		let height = (y + row_height).next_power_of_two();
+		if width > MAX_SIZE || height > MAX_SIZE {
+			return Err(Error::invalid(
+				&file,
+				format!(
+					"the sprites need a {}x{} atlas, the maximum is {}x{}",
+					width, height, MAX_SIZE, MAX_SIZE
+				),
+			));
+		}
+
+		let mut image = image::RgbaImage::new(width, height);
+		let mut uv = BTreeMap::new();
+		for ((name, sprite), (x, y)) in sprites.iter().zip(positions) {
+			image::imageops::replace(&mut image, sprite, x, y);
+			// Images are flipped when loaded, so the UVs count from the bottom of the atlas.
+			// Same order as the vertices of a quad: bottom left, bottom right, top right, top left
+			let (w, h) = sprite.dimensions();
+			let (bottom, top) = (height - y - h, height - y);
+			uv.insert(
+				name.clone(),
+				vec![(x, bottom), (x + w, bottom), (x + w, top), (x, top)],
+			);
+		}
+
+		Ok(Self {
+			image,
+			data: TextureData {
+				file,
+				default_uv: uv.keys().next().unwrap().clone(),
+				uv,
+				animations: BTreeMap::new(),
+				// Sprites are usually pixel art, and mipmaps would blend them together
+				settings: TextureSettings::pixel_art(),
+				nine_slice: None,
+			},
+		})
+	}
+
+	/// Packs every image in a directory (not in the `FileLoader`, this is for tools), named after their file stem
+	pub fn pack_dir<P: AsRef<Path>, T: ToString>(dir: P, file: T, padding: u32) -> Result<Self> {
+		let dir = dir.as_ref();
+		let mut sprites = Vec::new();
+		for entry in fs::read_dir(dir).map_err(|e| Error::Io(dir.to_path_buf(), e))? {
+			let path = entry.map_err(|e| Error::Io(dir.to_path_buf(), e))?.path();
+			// Skip whatever isn't an image, like a RON file next to them
+			if !path.is_file() || image::ImageFormat::from_path(&path).is_err() {
+				continue;
+			}
+			let sprite = image::open(&path)
+				.map_err(|e| Error::decode(&path, e))?
+				.to_rgba();
+			let name = path.file_stem().unwrap().to_string_lossy().to_string();
+			sprites.push((name, sprite));
+		}
+		Self::pack(sprites, file, padding)
+	}
+
+	/// Writes the atlas image and its `TextureData` RON, which should go in the same textures dir
+	pub fn save<P: AsRef<Path>, R: AsRef<Path>>(&self, image_path: P, ron_path: R) -> Result<()> {
+		let (image_path, ron_path) = (image_path.as_ref(), ron_path.as_ref());
+		self.image
+			.save(image_path)
+			.map_err(|e| Error::write(image_path, e))?;
+		let ron = to_string_pretty(&self.data, PrettyConfig::default())
+			.map_err(|e| Error::write(ron_path, e))?;
+		fs::write(ron_path, ron).map_err(|e| Error::write(ron_path, e))
+	}
+}
@@ -0,0 +1,466 @@
+// Shared between the engine and build.rs (included with #[path]), so it can only use the build dependencies.
+// Cooked assets are decoded ahead of time by build.rs, so loading them from a pack is only a copy
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use wavefront_obj::{mtl, obj};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Extension added to the path of an OBJ file for its cooked mesh (`models/cube.obj.mesh`)
+pub const MESH_EXT: &str = "mesh";
+/// Extension added to the path of an image for its cooked texels (`textures/texture.png.rgba`)
+pub const IMAGE_EXT: &str = "rgba";
+
+// Every cooked file starts with this, followed by the version
+const MAGIC: &[u8; 4] = b"LTCK";
+// Increase it when a cooked format changes, so old packs are rejected instead of misread
+const VERSION: u32 = 1;
+
+// Characters with a glyph in the cooked font atlases: printable ASCII & Latin-1
+const ATLAS_CHARS: &[(char, char)] = &[(' ', '~'), ('\u{a0}', '\u{ff}')];
+const ATLAS_WIDTH: u32 = 512;
+
+/// Path of the cooked form of an asset, `path` being `/` separated
+pub fn cooked_path(path: &str, ext: &str) -> String {
+	format!("{}.{}", path, ext)
+}
+
+/// Extension of the glyph atlas of a font at a pixel size (`fonts/Roboto-Black.ttf.20.glyphs`)
+pub fn glyphs_ext(size: f32) -> String {
+	format!("{}.glyphs", size)
+}
+
+#[allow(dead_code)] // Only used by build.rs
+pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+	let mut buf = MAGIC.to_vec();
+	buf.extend_from_slice(&VERSION.to_le_bytes());
+	buf.extend(bincode::serialize(value).map_err(|e| e.to_string())?);
+	Ok(buf)
+}
+
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, String> {
+	if data.len() < 8 || &data[..4] != MAGIC {
+		return Err("not a cooked asset".to_string());
+	}
+	let mut version = [0; 4];
+	version.copy_from_slice(&data[4..8]);
+	let version = u32::from_le_bytes(version);
+	if version != VERSION {
+		return Err(format!(
+			"cooked with version {}, expected {} (rebuild the pack)",
+			version, VERSION
+		));
+	}
+	bincode::deserialize(&data[8..]).map_err(|e| e.to_string())
+}
+
+/// RGBA8 texels, already flipped so the first row is the bottom one, like the textures expect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookedImage {
+	pub width: u32,
+	pub height: u32,
+	pub texels: Vec<u8>,
+}
+
+impl CookedImage {
+	/// `None` for 16-bit images, which would lose their precision as RGBA8
+	#[allow(dead_code)] // Only used by build.rs
+	pub fn new<P: AsRef<Path>>(data: &[u8], path: P) -> Result<Option<Self>, String> {
+		let format = image::ImageFormat::from_path(path).map_err(|e| e.to_string())?;
+		let img = image::load_from_memory_with_format(data, format).map_err(|e| e.to_string())?;
+		match img.color() {
+			image::ColorType::L16
+			| image::ColorType::La16
+			| image::ColorType::Rgb16
+			| image::ColorType::Rgba16 => return Ok(None),
+			_ => (),
+		}
+		let img = img.flipv().to_rgba();
+		Ok(Some(Self {
+			width: img.width(),
+			height: img.height(),
+			texels: img.into_raw(),
+		}))
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookedMaterial {
+	pub name: String,
+	pub specular_coefficient: f64,
+	pub color_ambient: [f64; 3],
+	pub color_diffuse: [f64; 3],
+	pub color_specular: [f64; 3],
+	pub color_emissive: Option<[f64; 3]>,
+	pub optical_density: Option<f64>,
+	pub alpha: f64,
+	pub illumination: u8, // 0: Ambient, 1: AmbientDiffuse, 2: AmbientDiffuseSpecular
+	pub uv_map: Option<String>,
+}
+
+fn color_array(c: &mtl::Color) -> [f64; 3] {
+	[c.r, c.g, c.b]
+}
+
+fn array_color(c: [f64; 3]) -> mtl::Color {
+	mtl::Color {
+		r: c[0],
+		g: c[1],
+		b: c[2],
+	}
+}
+
+impl From<&mtl::Material> for CookedMaterial {
+	fn from(m: &mtl::Material) -> Self {
+		Self {
+			name: m.name.clone(),
+			specular_coefficient: m.specular_coefficient,
+			color_ambient: color_array(&m.color_ambient),
+			color_diffuse: color_array(&m.color_diffuse),
+			color_specular: color_array(&m.color_specular),
+			color_emissive: m.color_emissive.as_ref().map(color_array),
+			optical_density: m.optical_density,
+			alpha: m.alpha,
+			illumination: match m.illumination {
+				mtl::Illumination::Ambient => 0,
+				mtl::Illumination::AmbientDiffuse => 1,
+				mtl::Illumination::AmbientDiffuseSpecular => 2,
+			},
+			uv_map: m.uv_map.clone(),
+		}
+	}
+}
+
+impl From<CookedMaterial> for mtl::Material {
+	fn from(m: CookedMaterial) -> Self {
+		Self {
+			name: m.name,
+			specular_coefficient: m.specular_coefficient,
+			color_ambient: array_color(m.color_ambient),
+			color_diffuse: array_color(m.color_diffuse),
+			color_specular: array_color(m.color_specular),
+			color_emissive: m.color_emissive.map(array_color),
+			optical_density: m.optical_density,
+			alpha: m.alpha,
+			illumination: match m.illumination {
+				0 => mtl::Illumination::Ambient,
+				1 => mtl::Illumination::AmbientDiffuse,
+				_ => mtl::Illumination::AmbientDiffuseSpecular,
+			},
+			uv_map: m.uv_map,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookedGeometry {
+	pub positions: Vec<[f32; 3]>,
+	pub normals: Vec<[f32; 3]>,
+	pub indices: Vec<u32>,
+	pub material: CookedMaterial,
+}
+
+/// Triangle meshes with their vertices deduplicated, ready to be uploaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookedMesh {
+	pub geometries: Vec<CookedGeometry>,
+}
+
+/// Where in the OBJ or MTL file the mesh couldn't be cooked
+#[derive(Debug, Clone)]
+pub struct MeshError {
+	pub in_mtl: bool,
+	pub line: Option<usize>,
+	pub msg: String,
+}
+
+impl MeshError {
+	fn obj<T: ToString>(msg: T) -> Self {
+		Self {
+			in_mtl: false,
+			line: None,
+			msg: msg.to_string(),
+		}
+	}
+}
+
+/// The material library an OBJ file uses and its line, it has to be found before parsing the whole file
+pub fn mtl_lib(obj: &str) -> Option<(usize, String)> {
+	obj.lines()
+		.map(|l| l.trim())
+		.enumerate()
+		.find(|(_, l)| l.starts_with("mtllib "))
+		.map(|(i, l)| (i + 1, l["mtllib ".len()..].trim().to_string()))
+}
+
+/// Where the material library of an OBJ file is: next to it, whether it's loaded at runtime or cooked
+pub fn mtl_path(obj_path: &Path, mtl_lib: &str) -> PathBuf {
+	obj_path.parent().unwrap_or(Path::new("")).join(mtl_lib)
+}
+
+// The parsed OBJ doesn't keep the lines, so this gives the line of every shape, in the order they're parsed.
+// A face of n corners is split in n - 2 triangles, a line or a face of 1 or 2 corners is a single shape
+fn shape_lines(obj: &str) -> Vec<usize> {
+	let mut lines = Vec::new();
+	for (i, l) in obj.lines().enumerate() {
+		let mut words = l.split_whitespace();
+		if let Some("f") | Some("l") = words.next() {
+			let shapes = words.count().saturating_sub(2).max(1);
+			lines.extend(std::iter::repeat(i + 1).take(shapes));
+		}
+	}
+	lines
+}
+
+fn default_material() -> CookedMaterial {
+	CookedMaterial {
+		name: "Default".into(),
+		specular_coefficient: 225.0,
+		color_ambient: [1.0, 1.0, 1.0],
+		color_diffuse: [0.001174, 0.0, 0.8],
+		color_specular: [0.5, 0.5, 0.5],
+		color_emissive: Some([0.0, 0.0, 0.0]),
+		optical_density: Some(1.45),
+		alpha: 1.0,
+		illumination: 2,
+		uv_map: None,
+	}
+}
+
+impl CookedMesh {
+	/// Parses the OBJ & MTL files, doesn't need the GPU or the `FileLoader`
+	pub fn from_obj(obj: String, mtl: Option<String>) -> Result<Self, MeshError> {
+		let obj_set = obj::parse(&obj).map_err(|e| MeshError {
+			in_mtl: false,
+			line: Some(e.line_number),
+			msg: e.message,
+		})?;
+
+		let mtl = if let Some(content) = mtl {
+			Some(mtl::parse(content).map_err(|e| MeshError {
+				in_mtl: true,
+				line: Some(e.line_number),
+				msg: e.message,
+			})?)
+		} else {
+			None
+		};
+
+		let objects = obj_set.objects;
+		if objects.len() != 1 {
+			return Err(MeshError::obj(format!(
+				"expecting a single object, found {}",
+				objects.len()
+			)));
+		}
+		let object = objects.into_iter().next().unwrap();
+
+		let shape_lines = shape_lines(&obj);
+		let mut shape_index = 0;
+		let mut geometries = Vec::new();
+		for geometry in object.geometry {
+			let material = mtl
+				.as_ref()
+				.and_then(|mtl_lib| {
+					let mtl_name = geometry.material_name.as_ref()?;
+					// The last material with the name wins
+					mtl_lib.materials.iter().rev().find(|m| &m.name == mtl_name)
+				})
+				.map(CookedMaterial::from)
+				.unwrap_or_else(default_material);
+
+			// build up vertices; for this to work, we remove duplicated vertices by putting them in a
+			// map associating the vertex with its ID
+			let mut vertex_cache: HashMap<obj::VTNIndex, u32> = HashMap::new();
+			let mut positions = Vec::new();
+			let mut normals = Vec::new();
+			let mut indices = Vec::new();
+
+			for shape in geometry.shapes {
+				let line = shape_lines.get(shape_index).copied();
+				shape_index += 1;
+				let error = |msg: &str| MeshError {
+					in_mtl: false,
+					line,
+					msg: msg.into(),
+				};
+				if let obj::Primitive::Triangle(a, b, c) = shape.primitive {
+					for key in &[a, b, c] {
+						if let Some(vertex_index) = vertex_cache.get(key) {
+							indices.push(*vertex_index);
+						} else {
+							let p = object
+								.vertices
+								.get(key.0)
+								.ok_or_else(|| error("vertex index out of range"))?;
+							let normal =
+								key.2.ok_or_else(|| error("missing normal for a vertex"))?;
+							let n = object
+								.normals
+								.get(normal)
+								.ok_or_else(|| error("normal index out of range"))?;
+							let vertex_index = positions.len() as u32;
+							positions.push([p.x as f32, p.y as f32, p.z as f32]);
+							normals.push([n.x as f32, n.y as f32, n.z as f32]);
+
+							vertex_cache.insert(*key, vertex_index);
+							indices.push(vertex_index);
+						}
+					}
+				} else {
+					return Err(error("unsupported non-triangle shape"));
+				}
+			}
+
+			geometries.push(CookedGeometry {
+				positions,
+				normals,
+				indices,
+				material,
+			})
+		}
+		Ok(Self { geometries })
+	}
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CookedGlyph {
+	pub advance: f32,
+	pub bounds: Option<[i32; 4]>, // Min x, min y, max x, max y, relative to the origin on the baseline
+	pub atlas_pos: [u32; 2],
+}
+
+/// Every glyph of a font rasterized at a single size, with the metrics needed to lay out a string
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CookedFont {
+	pub size: f32,
+	pub ascent: f32,
+	pub glyphs: HashMap<char, CookedGlyph>,
+	pub kerning: HashMap<(char, char), f32>, // Only the pairs that aren't 0
+	pub atlas_size: [u32; 2],
+	pub atlas: Vec<u8>, // Coverage, from 0 to 255
+}
+
+impl CookedFont {
+	#[allow(dead_code)] // Only used by build.rs
+	pub fn new(data: Vec<u8>, size: f32) -> Result<Self, String> {
+		let font = rusttype::Font::try_from_vec(data).ok_or("not a valid font")?;
+		let scale = rusttype::Scale::uniform(size);
+		let chars: Vec<char> = ATLAS_CHARS
+			.iter()
+			.flat_map(|&(a, b)| (a as u32..=b as u32).filter_map(std::char::from_u32))
+			.filter(|c| font.glyph(*c).id().0 != 0) // Skip the ones missing in the font
+			.collect();
+
+		// Shelf packing, glyphs are small and about the same height
+		let mut glyphs = HashMap::new();
+		let mut coverage = Vec::new();
+		let (mut x, mut y, mut row_height) = (0, 0, 0);
+		for &c in &chars {
+			let glyph = font
+				.glyph(c)
+				.scaled(scale)
+				.positioned(rusttype::point(0.0, 0.0));
+			let advance = glyph.unpositioned().h_metrics().advance_width;
+			let bounds = glyph.pixel_bounding_box();
+			let mut atlas_pos = [0, 0];
+			if let Some(bb) = bounds {
+				let (w, h) = (bb.width() as u32, bb.height() as u32);
+				if x + w > ATLAS_WIDTH {
+					x = 0;
+					y += row_height + 1;
+					row_height = 0;
+				}
+				atlas_pos = [x, y];
+				let mut pixels = vec![0; (w * h) as usize];
+				glyph.draw(|gx, gy, v| pixels[(gy * w + gx) as usize] = (v * 255.) as u8);
+				coverage.push((atlas_pos, w, pixels));
+				x += w + 1;
+				row_height = row_height.max(h);
+			}
+			glyphs.insert(
+				c,
+				CookedGlyph {
+					advance,
+					bounds: bounds.map(|bb| [bb.min.x, bb.min.y, bb.max.x, bb.max.y]),
+					atlas_pos,
+				},
+			);
+		}
+
+		let atlas_size = [ATLAS_WIDTH, y + row_height];
+		let mut atlas = vec![0; (atlas_size[0] * atlas_size[1]) as usize];
+		for ([x, y], w, pixels) in coverage {
+			for (i, row) in pixels.chunks(w as usize).enumerate() {
+				let start = ((y + i as u32) * ATLAS_WIDTH + x) as usize;
+				atlas[start..start + w as usize].copy_from_slice(row);
+			}
+		}
+
+		let mut kerning = HashMap::new();
+		for &a in &chars {
+			for &b in &chars {
+				let k = font.pair_kerning(scale, a, b);
+				if k != 0.0 {
+					kerning.insert((a, b), k);
+				}
+			}
+		}
+
+		Ok(Self {
+			size,
+			ascent: font.v_metrics(scale).ascent,
+			glyphs,
+			kerning,
+			atlas_size,
+			atlas,
+		})
+	}
+
+	/// Rasterizes a string, like rusttype does with the font. `None` if a char isn't in the atlas
+	pub fn render(&self, s: &str) -> Option<Vec<Vec<u8>>> {
+		let mut caret = 0.0;
+		let mut last = None;
+		let mut positioned = Vec::new();
+		for c in s.chars() {
+			let glyph = self.glyphs.get(&c)?;
+			if let Some(last) = last {
+				caret += self.kerning.get(&(last, c)).cloned().unwrap_or(0.0);
+			}
+			positioned.push((caret, glyph));
+			caret += glyph.advance;
+			last = Some(c);
+		}
+
+		let width = positioned
+			.last()
+			.map(|(x, g)| x + g.advance)
+			.unwrap_or(0.0)
+			.ceil() as usize;
+		let height = self.size.ceil() as usize;
+		let mut map = vec![vec![0; width]; height];
+		for (x, glyph) in positioned {
+			if let Some([min_x, min_y, max_x, max_y]) = glyph.bounds {
+				let left = x.round() as i32 + min_x;
+				let top = self.ascent.round() as i32 + min_y;
+				for gy in 0..(max_y - min_y) {
+					for gx in 0..(max_x - min_x) {
+						let (px, py) = (left + gx, top + gy);
+						// The glyph can still clip the boundaries of the bitmap
+						if px >= 0 && px < width as i32 && py >= 0 && py < height as i32 {
+							let ax = glyph.atlas_pos[0] + gx as u32;
+							let ay = glyph.atlas_pos[1] + gy as u32;
+							let v = self.atlas[(ay * self.atlas_size[0] + ax) as usize];
+							let p = &mut map[py as usize][px as usize];
+							*p = (*p).max(v);
+						}
+					}
+				}
+			}
+		}
+		Some(map)
+	}
+}
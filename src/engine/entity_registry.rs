@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 pub struct EntityRegistry<E> {
 	entities: HashMap<String, E>,
+	order: Vec<String>, // Names in the order they were registered, so HUD entities are drawn in that order
 }
 
 #[allow(dead_code)]
@@ -9,11 +10,22 @@ impl<E> EntityRegistry<E> {
 	pub fn new() -> Self {
 		Self {
 			entities: HashMap::new(),
+			order: Vec::new(),
 		}
 	}
 
+	/// Replacing an entity keeps its place in the order
 	pub fn register<T: ToString>(&mut self, name: &T, e: E) {
-		self.entities.insert(name.to_string(), e);
+		let name = name.to_string();
+		if self.entities.insert(name.clone(), e).is_none() {
+			self.order.push(name);
+		}
+	}
+
+	pub fn remove<T: ToString>(&mut self, name: &T) -> Option<E> {
+		let name = name.to_string();
+		self.order.retain(|n| *n != name);
+		self.entities.remove(&name)
 	}
 
 	pub fn get<T: ToString>(&self, name: &T) -> Option<&E> {
@@ -24,14 +36,31 @@ impl<E> EntityRegistry<E> {
 		self.entities.get_mut(&name.to_string())
 	}
 
+	/// In the order they were registered
 	pub fn values(&self) -> Vec<&E> {
-		self.entities.values().collect::<Vec<&E>>()
+		self.order
+			.iter()
+			.map(|n| &self.entities[n])
+			.collect::<Vec<&E>>()
+	}
+
+	/// In the order they were registered too
+	pub fn values_mut(&mut self) -> Vec<&mut E> {
+		let mut entities = self
+			.entities
+			.iter_mut()
+			.map(|(n, e)| (n.as_str(), e))
+			.collect::<HashMap<&str, &mut E>>();
+		self.order
+			.iter()
+			.filter_map(|n| entities.remove(n.as_str()))
+			.collect::<Vec<&mut E>>()
 	}
 }
 
 impl<E> std::fmt::Debug for EntityRegistry<E> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-		write!(f, "{:?}", self.entities.keys())
+		write!(f, "{:?}", self.order)
 	}
 }
 
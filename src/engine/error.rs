@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+use super::pack::IntegrityError;
+
+/// Every error that can happen while loading an asset, or changing a HUD entity, widget or scene
+#[derive(Debug)]
+pub enum Error {
+	/// The file isn't in any layer of the `FileLoader`
+	NotFound(PathBuf),
+	/// The file is in a pack, but doesn't match the pack manifest
+	Corrupt(String, IntegrityError), // Layer name, error
+	Io(PathBuf, std::io::Error),
+	/// The file is there but isn't a valid image, font, sound or pack
+	Decode(PathBuf, String),
+	/// Syntax errors in text files (RON, OBJ, MTL), with the line if it's known
+	Parse {
+		path: PathBuf,
+		line: Option<usize>,
+		msg: String,
+	},
+	/// The file was parsed, but its contents don't make sense
+	Invalid(PathBuf, String),
+	/// Creating or uploading a texture or a tess failed
+	Upload(Option<PathBuf>, String),
+	/// A tool couldn't write its output
+	Write(PathBuf, String),
+	/// There's no state, animation, entity or widget with that name
+	Unknown {
+		kind: &'static str,
+		name: String,
+	},
+	/// The entity or widget can't do that, like setting the value of a label
+	WrongKind {
+		name: String,
+		expected: &'static str,
+	},
+	/// Parenting an entity of a `Scene` to itself or one of its descendants
+	Cycle {
+		name: String,
+		parent: String,
+	},
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+	pub fn decode<P: AsRef<Path>, E: std::fmt::Display>(path: P, e: E) -> Self {
+		Self::Decode(path.as_ref().to_path_buf(), e.to_string())
+	}
+
+	pub fn parse<P: AsRef<Path>, T: ToString>(path: P, line: Option<usize>, msg: T) -> Self {
+		Self::Parse {
+			path: path.as_ref().to_path_buf(),
+			line,
+			msg: msg.to_string(),
+		}
+	}
+
+	pub fn invalid<P: AsRef<Path>, T: ToString>(path: P, msg: T) -> Self {
+		Self::Invalid(path.as_ref().to_path_buf(), msg.to_string())
+	}
+
+	pub fn write<P: AsRef<Path>, E: std::fmt::Display>(path: P, e: E) -> Self {
+		Self::Write(path.as_ref().to_path_buf(), e.to_string())
+	}
+
+	pub fn upload<E: std::fmt::Display>(path: Option<&Path>, e: E) -> Self {
+		Self::Upload(path.map(|p| p.to_path_buf()), e.to_string())
+	}
+
+	pub fn unknown<T: ToString>(kind: &'static str, name: T) -> Self {
+		Self::Unknown {
+			kind,
+			name: name.to_string(),
+		}
+	}
+
+	pub fn wrong_kind<T: ToString>(name: T, expected: &'static str) -> Self {
+		Self::WrongKind {
+			name: name.to_string(),
+			expected,
+		}
+	}
+
+	/// The asset the error is about, if there's one
+	pub fn path(&self) -> Option<&Path> {
+		match self {
+			Self::NotFound(path)
+			| Self::Io(path, _)
+			| Self::Decode(path, _)
+			| Self::Parse { path, .. }
+			| Self::Invalid(path, _)
+			| Self::Write(path, _) => Some(path.as_path()),
+			Self::Corrupt(_, _)
+			| Self::Unknown { .. }
+			| Self::WrongKind { .. }
+			| Self::Cycle { .. } => None,
+			Self::Upload(path, _) => path.as_ref().map(|p| p.as_path()),
+		}
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		match self {
+			Self::NotFound(path) => write!(f, "Can't find {:?}", path),
+			Self::Corrupt(layer, e) => write!(f, "Corrupt file in {}: {}", layer, e),
+			Self::Io(path, e) => write!(f, "Can't read {:?}: {}", path, e),
+			Self::Decode(path, msg) => write!(f, "Can't decode {:?}: {}", path, msg),
+			Self::Parse {
+				path,
+				line: Some(line),
+				msg,
+			} => write!(f, "Can't parse {:?} (line {}): {}", path, line, msg),
+			Self::Parse {
+				path,
+				line: None,
+				msg,
+			} => write!(f, "Can't parse {:?}: {}", path, msg),
+			Self::Invalid(path, msg) => write!(f, "Invalid {:?}: {}", path, msg),
+			Self::Upload(Some(path), msg) => {
+				write!(f, "Can't upload {:?} to the GPU: {}", path, msg)
+			}
+			Self::Upload(None, msg) => write!(f, "GPU upload error: {}", msg),
+			Self::Write(path, msg) => write!(f, "Can't write {:?}: {}", path, msg),
+			Self::Unknown { kind, name } => write!(f, "There's no {} named {:?}", kind, name),
+			Self::WrongKind { name, expected } => write!(f, "{:?} isn't a {}", name, expected),
+			Self::Cycle { name, parent } => write!(
+				f,
+				"Can't put {:?} under {:?}, it would be its own ancestor",
+				name, parent
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(_, e) => Some(e),
+			_ => None,
+		}
+	}
+}
@@ -0,0 +1,136 @@
+use super::super::texture::{Animation, Frame, LoopMode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationEvent {
+	Event(String),         // Event of a frame that started
+	Finished(String),      // Name of the animation, only for `LoopMode::Once`
+	TweenFinished(String), // Name of the tween
+}
+
+/// Plays an `Animation` over time, keeping track of the current frame
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+	name: String,
+	frames: Vec<Frame>, // With ping pong, the frames going backwards are added at the end
+	mode: LoopMode,
+	frame: usize,
+	frame_time: f32, // Time since the current frame started
+	playing: bool,
+	finished: bool,
+}
+
+#[allow(dead_code)]
+impl AnimationPlayer {
+	pub fn new<T: ToString>(name: T, animation: &Animation) -> Self {
+		let mut frames = animation.frames.clone();
+		if animation.mode == LoopMode::PingPong && frames.len() > 2 {
+			// The first and last frames aren't repeated when turning around
+			let back = frames[1..frames.len() - 1]
+				.iter()
+				.rev()
+				.cloned()
+				.collect::<Vec<_>>();
+			frames.extend(back);
+		}
+		Self {
+			name: name.to_string(),
+			frames,
+			mode: animation.mode,
+			frame: 0,
+			frame_time: 0.0,
+			playing: true,
+			finished: false,
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// UV state of the current frame
+	pub fn state(&self) -> &str {
+		&self.frames[self.frame].state
+	}
+
+	/// Event of the current frame, which isn't sent by `update` for the first frame
+	pub fn event(&self) -> Option<&str> {
+		self.frames[self.frame].event.as_ref().map(|e| e.as_str())
+	}
+
+	pub fn play(&mut self) {
+		if !self.finished {
+			self.playing = true
+		}
+	}
+
+	pub fn pause(&mut self) {
+		self.playing = false
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.playing
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	/// Length of a cycle, in seconds
+	pub fn duration(&self) -> f32 {
+		self.frames.iter().map(|f| f.duration).sum()
+	}
+
+	/// Time since the start of the current cycle
+	pub fn time(&self) -> f32 {
+		self.frames[..self.frame]
+			.iter()
+			.map(|f| f.duration)
+			.sum::<f32>()
+			+ self.frame_time
+	}
+
+	/// Jumps to a time, without sending the events of the skipped frames.
+	/// Looping animations wrap around, the others stop at the end
+	pub fn seek(&mut self, time: f32) {
+		let duration = self.duration();
+		let mut time = match self.mode {
+			LoopMode::Once => time.max(0.0).min(duration),
+			LoopMode::Loop | LoopMode::PingPong => time.rem_euclid(duration),
+		};
+		self.finished = self.mode == LoopMode::Once && time >= duration;
+		if self.finished {
+			self.playing = false;
+		}
+		self.frame = 0;
+		while self.frame + 1 < self.frames.len() && time >= self.frames[self.frame].duration {
+			time -= self.frames[self.frame].duration;
+			self.frame += 1;
+		}
+		self.frame_time = time;
+	}
+
+	/// Advances the animation `dt` seconds, returns the events of the frames that started.
+	/// Several frames can be skipped in a single update, every event is sent anyway
+	pub fn update(&mut self, dt: f32) -> Vec<AnimationEvent> {
+		let mut events = Vec::new();
+		if !self.playing {
+			return events;
+		}
+		self.frame_time += dt;
+		while self.frame_time >= self.frames[self.frame].duration {
+			if self.frame + 1 == self.frames.len() && self.mode == LoopMode::Once {
+				self.frame_time = self.frames[self.frame].duration;
+				self.playing = false;
+				self.finished = true;
+				events.push(AnimationEvent::Finished(self.name.clone()));
+				break;
+			}
+			self.frame_time -= self.frames[self.frame].duration;
+			self.frame = (self.frame + 1) % self.frames.len();
+			if let Some(event) = self.event() {
+				events.push(AnimationEvent::Event(event.to_string()));
+			}
+		}
+		events
+	}
+}
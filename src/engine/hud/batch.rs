@@ -0,0 +1,272 @@
+use luminance::blending::{Equation, Factor};
+use luminance::context::GraphicsContext;
+use luminance::pipeline::{BoundTexture, Pipeline, ShadingGate, TessGate};
+use luminance::pixel::{Floating, NormUnsigned, Pixel};
+use luminance::render_state::RenderState;
+use luminance::shader::program::{Program, ProgramInterface, Uniform};
+use luminance::tess::{Mode as TessMode, Tess, TessBuilder, TessSliceIndex as _};
+use luminance::texture::{Dim2, Texture};
+
+use luminance_derive::{Semantics, UniformInterface, Vertex};
+
+use std::cell::Cell;
+
+use super::layer::layer_order;
+use super::Entity;
+
+use super::super::assets::Handle;
+use super::super::error::{Error, Result};
+use super::super::stats::RenderStats;
+use super::super::texture::AnyTexture;
+
+const VS_STR: &str = include_str!("shaders/batch_vs.glsl");
+const FS_STR: &str = include_str!("shaders/batch_fs.glsl");
+
+// Smallest vertex buffer of a batch, so small batches don't have to grow every time they change
+const MIN_CAPACITY: usize = 64;
+
+#[derive(UniformInterface)]
+pub struct BatchUniformInterface {
+	size: Uniform<[u32; 2]>,
+	#[uniform(unbound)]
+	tex: Uniform<&'static BoundTexture<'static, Dim2, NormUnsigned>>,
+	#[uniform(unbound)]
+	tex_floating: Uniform<&'static BoundTexture<'static, Dim2, Floating>>,
+	floating_tex: Uniform<bool>,
+	gray_tex: Uniform<bool>,
+	srgb_tex: Uniform<bool>,
+}
+
+#[derive(Copy, Clone, Debug, Semantics)]
+pub enum BatchSemantics {
+	#[sem(name = "position", repr = "[f32; 2]", wrapper = "BatchPosition")]
+	Position, // Pixels from the bottom left
+	#[sem(name = "uv", repr = "[f32; 2]", wrapper = "BatchUV")]
+	UV, // From 0 to 1
+	#[sem(name = "color", repr = "[f32; 4]", wrapper = "BatchColor")]
+	Color, // Tint, with the opacity in the alpha
+	#[sem(name = "clip", repr = "[f32; 4]", wrapper = "BatchClip")]
+	Clip, // Rectangle of the surface the entity is drawn in
+}
+
+/// Vertex of an entity with its placement already applied, so entities can share a buffer
+#[derive(Vertex, Clone, Debug)]
+#[vertex(sem = "BatchSemantics")]
+pub struct BatchVertex {
+	position: BatchPosition,
+	uv: BatchUV,
+	color: BatchColor,
+	clip: BatchClip,
+}
+
+// Consecutive entities with the same texture, drawn with a single call
+struct Batch {
+	tex: Handle<AnyTexture>,
+	tess: Tess,
+	capacity: usize, // Vertices in the buffer, only the first `len` are drawn
+	len: usize,
+	entities: Vec<(u64, u64)>, // ID and revision of the entities in the buffer
+}
+
+/// Draws HUD entities in as few calls as possible: in drawing order (see `Layer`), the ones next to each other
+/// with the same texture share a vertex buffer, which is only uploaded again when one of them changes
+pub struct Batcher {
+	program: Program<BatchSemantics, (), BatchUniformInterface>,
+	render_st: RenderState,
+	batches: Vec<Batch>,
+	size: [u32; 2],
+	stats: Cell<RenderStats>,
+}
+
+#[allow(dead_code)]
+impl Batcher {
+	pub fn new() -> Self {
+		let program: Program<BatchSemantics, (), BatchUniformInterface> =
+			Program::from_strings(None, VS_STR, None, FS_STR)
+				.expect("Error loading HUD batch shaders")
+				.ignore_warnings();
+		// Drawn in order, the depth buffer would hide the entities drawn later
+		let render_st = RenderState::default()
+			.set_blending((
+				Equation::Additive,
+				Factor::SrcAlpha,
+				Factor::SrcAlphaComplement,
+			))
+			.set_depth_test(None);
+		Self {
+			program,
+			render_st,
+			batches: Vec::new(),
+			size: [0, 0],
+			stats: Cell::new(RenderStats::default()),
+		}
+	}
+
+	/// Groups the entities into batches and uploads the ones that changed.
+	/// Call it every frame before the pipeline, it needs the surface to create buffers
+	pub fn prepare<C: GraphicsContext>(
+		&mut self,
+		surface: &mut C,
+		entities: Vec<&Entity>,
+		size: [u32; 2],
+	) -> Result<()> {
+		let entities = layer_order(entities.into_iter().filter(|e| e.is_visible()));
+		let resized = size != self.size;
+		self.size = size;
+		let mut stats = self.stats.get();
+		stats.entities += entities.len();
+
+		let mut groups: Vec<Vec<&Entity>> = Vec::new();
+		for entity in entities {
+			match groups.last_mut() {
+				Some(group) if Handle::ptr_eq(group[0].texture(), entity.texture()) => {
+					group.push(entity)
+				}
+				_ => groups.push(vec![entity]),
+			}
+		}
+
+		let mut vertices = Vec::new();
+		for (i, group) in groups.iter().enumerate() {
+			let key = group
+				.iter()
+				.map(|e| (e.id(), e.revision()))
+				.collect::<Vec<_>>();
+			let tex = group[0].texture();
+			if let Some(batch) = self.batches.get(i) {
+				if !resized && batch.entities == key && Handle::ptr_eq(&batch.tex, tex) {
+					continue;
+				}
+			}
+
+			vertices.clear();
+			for entity in group {
+				entity.batch_vertices(size, &mut vertices);
+			}
+			stats.uploads += 1;
+			match self.batches.get_mut(i) {
+				Some(batch) if batch.capacity >= vertices.len() => {
+					let mut slice = batch
+						.tess
+						.as_slice_mut::<BatchVertex>()
+						.map_err(|e| Error::upload(None, format!("{:?}", e)))?;
+					slice[..vertices.len()].clone_from_slice(&vertices);
+					batch.tex = tex.clone();
+					batch.len = vertices.len();
+					batch.entities = key;
+				}
+				_ => {
+					let batch = Self::batch(surface, tex.clone(), &vertices, key)?;
+					if i < self.batches.len() {
+						self.batches[i] = batch;
+					} else {
+						self.batches.push(batch);
+					}
+				}
+			}
+		}
+		self.batches.truncate(groups.len());
+		self.stats.set(stats);
+		Ok(())
+	}
+
+	// A new batch, with room for twice the vertices it has
+	fn batch<C: GraphicsContext>(
+		surface: &mut C,
+		tex: Handle<AnyTexture>,
+		vertices: &[BatchVertex],
+		entities: Vec<(u64, u64)>,
+	) -> Result<Batch> {
+		let capacity = (vertices.len() * 2).max(MIN_CAPACITY);
+		let mut data = vertices.to_vec();
+		data.resize(
+			capacity,
+			BatchVertex::new(
+				BatchPosition::new([0.0; 2]),
+				BatchUV::new([0.0; 2]),
+				BatchColor::new([0.0; 4]),
+				BatchClip::new([0.0; 4]),
+			),
+		);
+		let tess = TessBuilder::new(surface)
+			.add_vertices(data)
+			.set_mode(TessMode::Triangle)
+			.build()
+			.map_err(|e| Error::upload(None, format!("{:?}", e)))?;
+		Ok(Batch {
+			tex,
+			tess,
+			capacity,
+			len: vertices.len(),
+			entities,
+		})
+	}
+
+	/// What was prepared and drawn since the last call
+	pub fn take_stats(&self) -> RenderStats {
+		self.stats.take()
+	}
+
+	/// Draws the batches of the last `prepare`
+	pub fn render<C: GraphicsContext>(
+		&self,
+		shd_gate: &mut ShadingGate<'_, C>,
+		pipeline: &Pipeline,
+	) {
+		let mut stats = self.stats.get();
+		stats.draw_calls += self.batches.iter().filter(|b| b.len > 0).count();
+		self.stats.set(stats);
+		shd_gate.shade(&self.program, |iface, mut rdr_gate| {
+			iface.size.update(self.size);
+			rdr_gate.render(&self.render_st, |mut tess_gate| {
+				for batch in self.batches.iter().filter(|b| b.len > 0) {
+					iface.gray_tex.update(match &*batch.tex {
+						AnyTexture::R8(_) | AnyTexture::R16(_) => true,
+						_ => false,
+					});
+					iface.srgb_tex.update(match &*batch.tex {
+						AnyTexture::Srgba8(_) => true,
+						_ => false,
+					});
+					// Same samplers as `Entity::render`
+					match &*batch.tex {
+						AnyTexture::Rgba8(tex) => {
+							render_norm(pipeline, &iface, &mut tess_gate, batch, tex)
+						}
+						AnyTexture::Srgba8(tex) => {
+							render_norm(pipeline, &iface, &mut tess_gate, batch, tex)
+						}
+						AnyTexture::R8(tex) => {
+							render_norm(pipeline, &iface, &mut tess_gate, batch, tex)
+						}
+						AnyTexture::R16(tex) => {
+							render_norm(pipeline, &iface, &mut tess_gate, batch, tex)
+						}
+						AnyTexture::Rgba16(tex) => {
+							render_norm(pipeline, &iface, &mut tess_gate, batch, tex)
+						}
+						AnyTexture::RgbF32(tex) => {
+							let bound_tex = pipeline.bind_texture(tex);
+							iface.tex_floating.update(&bound_tex);
+							iface.floating_tex.update(true);
+							tess_gate.render(batch.tess.slice(..batch.len));
+						}
+					}
+				}
+			})
+		});
+	}
+}
+
+fn render_norm<C: GraphicsContext, P: Pixel<SamplerType = NormUnsigned>>(
+	pipeline: &Pipeline,
+	iface: &ProgramInterface<'_, BatchUniformInterface>,
+	tess_gate: &mut TessGate<C>,
+	batch: &Batch,
+	tex: &Texture<Dim2, P>,
+) {
+	let bound_tex = pipeline.bind_texture(tex);
+	iface.tex.update(&bound_tex);
+	iface.floating_tex.update(false);
+	tess_gate.render(batch.tess.slice(..batch.len));
+}
@@ -21,7 +21,6 @@ pub struct Entity {
 	tex_size: [u32; 2],
 	scale: f32,
 	pos: [i32; 2],
-	depth: f32,
 	uv_states: Option<HashMap<String, Vec<VertexUV>>>, // ID: [VertexUV]
 }
 
@@ -45,15 +44,10 @@ impl Entity {
 			tex_size,
 			scale: 1.0,
 			pos: [0, 0],
-			depth: 0.0,
 			uv_states: None,
 		};
 	}
 
-	pub fn get_depth(&self) -> f32 {
-		self.depth
-	}
-
 	pub fn render<C: GraphicsContext>(
 		&self,
 		pipeline: &Pipeline,
@@ -67,9 +61,15 @@ impl Entity {
 		iface.tex_floating.update(&bound_tex);
 		iface.size.update(size.clone().into());
 		iface.pos.update(self.pos.into());
-		iface.depth.update(self.depth.into());
 		iface.scale.update(self.scale.into());
 		iface.tex_size.update(self.tex_size.into());
+		// Uniforms keep the values of the last entity
+		iface.rotation.update(0.0);
+		iface.tint.update([1.0; 4]);
+		iface.opacity.update(1.0);
+		iface
+			.clip
+			.update([0.0, 0.0, size[0] as f32, size[1] as f32]);
 
 		tess_gate.render(self.vao.slice(..));
 	}
@@ -5,15 +5,24 @@ use luminance::shader::program::ProgramInterface;
 use luminance::tess::{Mode as TessMode, Tess, TessBuilder, TessSliceIndex as _};
 use luminance::texture::{Dim2, Texture};
 
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use super::animation::{AnimationEvent, AnimationPlayer};
+use super::batch::{BatchClip, BatchColor, BatchPosition, BatchUV, BatchVertex};
+use super::layer::Layer;
+use super::layout::{intersect_clip, Anchor, Layout};
+use super::scene::Parent;
+use super::tween::Tween;
 use super::{HudUniformInterface, Vertex, VertexPosition, VertexUV};
 
+use super::super::assets::{AssetServer, Handle};
+use super::super::error::{Error, Result};
 use super::super::text::{tex_from_string, Font};
-use super::super::texture::TextureData;
+use super::super::texture::{Animation, AnyTexture, NineSlice, TextureData};
 use super::super::utils::*;
-use super::super::TEXTURES_PATH;
+use super::super::{FONTS_PATH, TEXTURES_PATH};
 // use super::super::renderer::{Renderable, HasDepth};
 
 pub enum EntityKind {
@@ -21,29 +30,59 @@ pub enum EntityKind {
 	Text,
 }
 
+// IDs of the entities, so the `Batcher` can tell them apart
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Where the texture of an entity came from, so it can be rebuilt when the files change
+#[derive(Debug, Clone)]
+enum Source {
+	TextureData(PathBuf), // RON file, relative to TEXTURES_PATH
+	Text(String, Font),
+}
+
 pub struct Entity {
+	id: u64,
+	revision: u64, // Increased every time the way it's drawn changes
 	vao: Tess,
-	tex: Texture<Dim2, NormRGBA8UI>,
+	vertices: Vec<Vertex>, // Copy of the vertices and indices of the VAO, for batching
+	indices: Vec<u8>,
+	tex: Handle<AnyTexture>,
 	tex_size: [u32; 2],
 	scale: f32,
 	pos: [i32; 2],
-	depth: f32,
+	layer: Option<Layer>, // Inherited from the parent if it's not set
+	extent: [i32; 4],     // Min x, min y, max x, max y of the vertices, without scaling
+	layout: Option<Layout>,
+	parent: Option<Parent>, // Set by the `Scene`, the entity is placed relative to it
+	visible: bool,
+	clip: Option<[i32; 4]>, // On the surface, the parts outside of it aren't drawn
+	clips_children: bool,
+	rotation: f32,   // Radians, counterclockwise
+	pivot: Anchor,   // Point of the extent the entity rotates around
+	tint: RgbaColor, // Multiplies the texture color
+	opacity: f32,
 	uv_states: Option<HashMap<String, Vec<VertexUV>>>, // ID: [VertexUV]
+	state: Option<String>,
+	animations: BTreeMap<String, Animation>,
+	animation: Option<AnimationPlayer>,
+	tweens: Vec<(String, Tween)>, // Played at the same time, in the order they were added
+	panel: Option<(NineSlice, [u32; 2])>, // Borders and size of a nine slice panel
+	source: Option<Source>,
+	dependencies: Vec<PathBuf>, // Files (relative to the assets dir) used to build the texture
 }
 
 impl Entity {
 	#[allow(dead_code)]
 	pub fn new<'p, C: GraphicsContext>(
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		vertices: &'p [Vertex],
 		indices: &'p [u8],
 		path: &Path,
-	) -> Option<Self> {
-		if let Some(img) = read_image(file_loader, path) {
-			return Some(Self::new_from_img(surface, vertices, indices, img));
-		}
-		None
+	) -> Result<Self> {
+		let tex = assets.texture(file_loader, surface, path)?;
+		Self::new_from_handle(surface, vertices, indices, tex)
 	}
 
 	#[allow(dead_code)]
@@ -52,104 +91,158 @@ impl Entity {
 		vertices: &'p [Vertex],
 		indices: &'p [u8],
 		img: image::RgbaImage,
-	) -> Self {
-		let tex = load_from_disk(surface, img);
+	) -> Result<Self> {
+		let tex = load_from_disk(surface, img)?;
 		return Self::new_from_tex(surface, vertices, indices, tex);
 	}
 
 	#[allow(dead_code)]
 	pub fn new_with_texture_data<'p, C: GraphicsContext>(
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		vertices_pos: &'p [VertexPosition],
 		indices: &'p [u8],
 		tex_data: TextureData,
-	) -> Self {
+	) -> Result<Self> {
+		let img_path = TEXTURES_PATH.join(&tex_data.file);
 		let mut vertices = Vec::new();
-		let def_uv = tex_data.uv.get(&tex_data.default_uv).expect(&format!(
-			"Default UV is not valid for texture: {}",
-			tex_data.file
-		));
+		let def_uv = tex_data.uv.get(&tex_data.default_uv).ok_or_else(|| {
+			Error::invalid(
+				&img_path,
+				format!("default UV \"{}\" doesn't exist", tex_data.default_uv),
+			)
+		})?;
+		if def_uv.len() < vertices_pos.len() {
+			return Err(Error::invalid(
+				&img_path,
+				format!("{} UVs for {} vertices", def_uv.len(), vertices_pos.len()),
+			));
+		}
 		for i in 0..vertices_pos.len() {
 			let uv = VertexUV::new([def_uv[i].0, def_uv[i].1]);
 			vertices.push(Vertex::new(vertices_pos[i], uv));
 		}
+		let tex =
+			assets.texture_with_settings(file_loader, surface, &img_path, &tex_data.settings)?;
+		let mut ret = Self::new_from_handle(surface, &vertices, indices, tex)?;
+
+		ret.uv_states = Some(Self::uv_states_from(&tex_data, None));
+		ret.state = Some(tex_data.default_uv.clone());
+		ret.animations = tex_data.animations;
+		ret.dependencies = vec![img_path];
+		Ok(ret)
+	}
+
+	/// Creates a nine slice panel of `size` pixels, from a `TextureData` RON with `nine_slice` borders.
+	/// Every UV state (and animation) of the RON is sliced with the same borders
+	#[allow(dead_code)]
+	pub fn panel<C: GraphicsContext>(
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		file: &Path,
+		size: [u32; 2],
+	) -> Result<Self> {
+		let tex_data = TextureData::load(file_loader, file)?;
+		let ron_path = TEXTURES_PATH.join(file);
+		let nine_slice = tex_data
+			.nine_slice
+			.ok_or_else(|| Error::invalid(&ron_path, "a panel needs nine_slice borders"))?;
+		let uv_states = Self::uv_states_from(&tex_data, Some(&nine_slice));
+		// The default UV was validated by `TextureData::load`
+		let vertices = nine_slice
+			.grid_pos(size)
+			.into_iter()
+			.zip(&uv_states[&tex_data.default_uv])
+			.map(|(pos, uv)| Vertex::new(VertexPosition::new(pos), *uv))
+			.collect::<Vec<_>>();
+		let img_path = TEXTURES_PATH.join(&tex_data.file);
+		let tex =
+			assets.texture_with_settings(file_loader, surface, &img_path, &tex_data.settings)?;
+		let mut ret = Self::new_from_handle(surface, &vertices, &NineSlice::grid_indices(), tex)?;
+
+		ret.uv_states = Some(uv_states);
+		ret.state = Some(tex_data.default_uv.clone());
+		ret.animations = tex_data.animations;
+		ret.panel = Some((nine_slice, size));
+		ret.source = Some(Source::TextureData(file.to_path_buf()));
+		ret.dependencies = vec![img_path, ron_path];
+		Ok(ret)
+	}
+
+	// With nine slice borders, every state has the UVs of the grid instead of its own
+	fn uv_states_from(
+		tex_data: &TextureData,
+		nine_slice: Option<&NineSlice>,
+	) -> HashMap<String, Vec<VertexUV>> {
 		let mut uv_states = HashMap::new();
-		for key in tex_data.uv.keys() {
-			let mut uvs = Vec::new();
-			for v in tex_data.uv.get(key).unwrap() {
-				uvs.push(VertexUV::new([v.0, v.1]))
-			}
+		for (key, uv) in &tex_data.uv {
+			let uv = match nine_slice {
+				Some(nine_slice) => nine_slice.grid_uv(uv),
+				None => uv.clone(),
+			};
+			let uvs = uv.iter().map(|v| VertexUV::new([v.0, v.1])).collect();
 			uv_states.insert(key.clone(), uvs);
 		}
-		let mut ret = Self::new(
-			file_loader,
-			surface,
-			&vertices,
-			indices,
-			TEXTURES_PATH.join(&tex_data.file).as_ref(),
-		)
-		.expect(&format!(
-			"Error creating simple entity for texture (IMAGE NOT FOUND): {:?}",
-			TEXTURES_PATH.join(&tex_data.file)
-		));
-
-		ret.uv_states = Some(uv_states);
-		ret
+		uv_states
 	}
 
 	#[allow(dead_code)]
 	pub fn load<'p, C: GraphicsContext>(
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		vertices_pos: &'p [VertexPosition],
 		indices: &'p [u8],
 		file: &Path,
-	) -> Option<Self> {
-		match TextureData::load(file_loader, file) {
-			Some(x) => Some(Self::new_with_texture_data(
-				file_loader,
-				surface,
-				vertices_pos,
-				indices,
-				x,
-			)),
-			None => None,
-		}
+	) -> Result<Self> {
+		let tex_data = TextureData::load(file_loader, file)?;
+		let mut ret = Self::new_with_texture_data(
+			assets,
+			file_loader,
+			surface,
+			vertices_pos,
+			indices,
+			tex_data,
+		)?;
+		ret.source = Some(Source::TextureData(file.to_path_buf()));
+		ret.dependencies.push(TEXTURES_PATH.join(file));
+		Ok(ret)
 	}
 
 	pub fn new_entity_from_string<'p, C: GraphicsContext>(
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		s: String,
 		font: &Font,
-	) -> Option<Self> {
-		if let Some((tex, uvs)) = tex_from_string(file_loader, surface, s.clone(), font) {
-			let [width, height] = tex.size();
-			return Some(Self::new_from_tex(
-				surface,
-				&[
-					Vertex::new(VertexPosition::new([0, 0]), VertexUV::new(uvs[0])),
-					Vertex::new(
-						VertexPosition::new([width as i32, 0]),
-						VertexUV::new(uvs[1]),
-					),
-					Vertex::new(
-						VertexPosition::new([width as i32, height as i32]),
-						VertexUV::new(uvs[2]),
-					),
-					Vertex::new(
-						VertexPosition::new([0, height as i32]),
-						VertexUV::new(uvs[3]),
-					),
-				],
-				&[0, 1, 2, 0, 2, 3],
-				tex,
-			));
-		} else {
-			eprintln!("Error creting texture for string \"{}\"", s)
-		}
-		None
+	) -> Result<Self> {
+		let (tex, uvs) = tex_from_string(assets, file_loader, surface, s.clone(), font)?;
+		let [width, height] = tex.size();
+		let mut ret = Self::new_from_tex(
+			surface,
+			&[
+				Vertex::new(VertexPosition::new([0, 0]), VertexUV::new(uvs[0])),
+				Vertex::new(
+					VertexPosition::new([width as i32, 0]),
+					VertexUV::new(uvs[1]),
+				),
+				Vertex::new(
+					VertexPosition::new([width as i32, height as i32]),
+					VertexUV::new(uvs[2]),
+				),
+				Vertex::new(
+					VertexPosition::new([0, height as i32]),
+					VertexUV::new(uvs[3]),
+				),
+			],
+			&[0, 1, 2, 0, 2, 3],
+			tex,
+		)?;
+		ret.source = Some(Source::Text(s, font.clone()));
+		ret.dependencies = vec![FONTS_PATH.join(font.name())];
+		return Ok(ret);
 	}
 
 	#[allow(dead_code)]
@@ -158,40 +251,280 @@ impl Entity {
 		vertices: &'p [Vertex],
 		indices: &'p [u8],
 		tex: Texture<Dim2, NormRGBA8UI>,
-	) -> Self {
+	) -> Result<Self> {
+		Self::new_from_handle(surface, vertices, indices, Handle::new(tex.into()))
+	}
+
+	/// Creates an entity using a shared texture
+	pub fn new_from_handle<'p, C: GraphicsContext>(
+		surface: &mut C,
+		vertices: &'p [Vertex],
+		indices: &'p [u8],
+		tex: Handle<AnyTexture>,
+	) -> Result<Self> {
 		let tess = TessBuilder::new(surface)
 			.add_vertices(vertices)
 			.set_indices(indices)
 			.set_mode(TessMode::Triangle)
 			.build()
-			.unwrap();
+			.map_err(|e| Error::upload(None, format!("{:?}", e)))?;
 		// println!("{},{}", width, height);
 		let size = tex.size();
-		return Self {
+		return Ok(Self {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+			revision: 0,
 			vao: tess,
+			vertices: vertices.to_vec(),
+			indices: indices.to_vec(),
 			tex: tex,
 			tex_size: size,
 			scale: 1.0,
 			pos: [0, 0],
-			depth: 0.0,
+			layer: None,
+			extent: extent(vertices.iter().map(|v| v.get_pos())),
+			layout: None,
+			parent: None,
+			visible: true,
+			clip: None,
+			clips_children: false,
+			rotation: 0.0,
+			pivot: Anchor::Center,
+			tint: RgbaColor::new(255, 255, 255, 255),
+			opacity: 1.0,
 			uv_states: None,
-		};
+			state: None,
+			animations: BTreeMap::new(),
+			animation: None,
+			tweens: Vec::new(),
+			panel: None,
+			source: None,
+			dependencies: Vec::new(),
+		});
 	}
 
+	/// Places the entity at absolute pixels from the bottom left, replacing its layout
 	pub fn set_pos(&mut self, new_pos: [i32; 2]) {
-		self.pos = new_pos
+		if self.pos != new_pos || self.layout.is_some() {
+			self.pos = new_pos;
+			self.layout = None;
+			self.touch();
+		}
+	}
+
+	/// Places the entity relative to the surface, following its size. See `Layout`
+	pub fn set_layout(&mut self, layout: Layout) {
+		if self.layout != Some(layout) {
+			self.layout = Some(layout);
+			self.touch();
+		}
+	}
+
+	pub fn layout(&self) -> Option<&Layout> {
+		self.layout.as_ref()
+	}
+
+	/// Position and scale the entity is rendered with on a surface of this size.
+	/// With a parent, the position and layout are relative to it, and its scale is applied on top
+	pub fn placement(&self, surface_size: [u32; 2]) -> ([i32; 2], f32) {
+		match (&self.layout, &self.parent) {
+			(Some(layout), None) => {
+				let scale = self.scale * layout.scale(surface_size);
+				let min = [self.extent[0] as f32 * scale, self.extent[1] as f32 * scale];
+				let max = [self.extent[2] as f32 * scale, self.extent[3] as f32 * scale];
+				(layout.position(surface_size, min, max), scale)
+			}
+			(None, None) => (self.pos, self.scale),
+			// Children follow the scale of their parent instead of the reference height
+			(Some(layout), Some(parent)) => {
+				let scale = self.scale * parent.scale;
+				let min = [self.extent[0] as f32 * scale, self.extent[1] as f32 * scale];
+				let max = [self.extent[2] as f32 * scale, self.extent[3] as f32 * scale];
+				let pos = layout.position_in(parent.size, parent.scale, min, max);
+				(
+					[parent.origin[0] + pos[0], parent.origin[1] + pos[1]],
+					scale,
+				)
+			}
+			(None, Some(parent)) => {
+				let pos = [
+					parent.origin[0] + (self.pos[0] as f32 * parent.scale).round() as i32,
+					parent.origin[1] + (self.pos[1] as f32 * parent.scale).round() as i32,
+				];
+				(pos, self.scale * parent.scale)
+			}
+		}
+	}
+
+	/// Min x, min y, max x, max y of the entity on a surface of this size
+	pub fn rect(&self, surface_size: [u32; 2]) -> [i32; 4] {
+		let (pos, scale) = self.placement(surface_size);
+		let scaled = |v: i32| (v as f32 * scale).round() as i32;
+		[
+			pos[0] + scaled(self.extent[0]),
+			pos[1] + scaled(self.extent[1]),
+			pos[0] + scaled(self.extent[2]),
+			pos[1] + scaled(self.extent[3]),
+		]
+	}
+
+	/// Places the entity relative to a parent, usually done by a `Scene`
+	pub fn set_parent(&mut self, parent: Option<Parent>) {
+		if self.parent != parent {
+			self.parent = parent;
+			self.touch();
+		}
+	}
+
+	pub fn parent(&self) -> Option<&Parent> {
+		self.parent.as_ref()
+	}
+
+	/// Hidden entities aren't drawn, neither are the children of hidden entities in a `Scene`
+	pub fn set_visible(&mut self, visible: bool) {
+		self.visible = visible
+	}
+
+	/// Whether the entity is drawn, which is false if its parent is hidden
+	pub fn is_visible(&self) -> bool {
+		self.visible && self.parent.map_or(true, |p| p.visible)
+	}
+
+	/// Only draws the part of the entity inside `rect` (min x, min y, max x, max y on the surface)
+	pub fn set_clip(&mut self, rect: Option<[i32; 4]>) {
+		if self.clip != rect {
+			self.clip = rect;
+			self.touch();
+		}
+	}
+
+	/// Where the entity is drawn: its own clip rectangle, inside the one of its parent
+	pub fn clip(&self) -> Option<[i32; 4]> {
+		intersect_clip(self.clip, self.parent.and_then(|p| p.clip))
+	}
+
+	/// In a `Scene`, only draws the children inside the rectangle of this entity, like a scroll view
+	pub fn set_clips_children(&mut self, clips: bool) {
+		self.clips_children = clips
+	}
+
+	pub fn clips_children(&self) -> bool {
+		self.clips_children
+	}
+
+	// The clip rectangle as floats, the whole surface if there's none
+	fn clip_array(&self, surface_size: [u32; 2]) -> [f32; 4] {
+		let [min_x, min_y, max_x, max_y] =
+			self.clip()
+				.unwrap_or([0, 0, surface_size[0] as i32, surface_size[1] as i32]);
+		[min_x as f32, min_y as f32, max_x as f32, max_y as f32]
+	}
+
+	/// Min x, min y, max x, max y of the vertices, without the position or scale
+	pub fn extent(&self) -> [i32; 4] {
+		self.extent
+	}
+
+	/// Rotates the entity counterclockwise around its pivot, doesn't change its layout
+	pub fn set_rotation(&mut self, radians: f32) {
+		if self.rotation != radians {
+			self.rotation = radians;
+			self.touch();
+		}
+	}
+
+	pub fn rotation(&self) -> f32 {
+		self.rotation
+	}
+
+	/// Point the entity rotates around, the center by default
+	pub fn set_pivot(&mut self, pivot: Anchor) {
+		if self.pivot != pivot {
+			self.pivot = pivot;
+			self.touch();
+		}
+	}
+
+	/// Multiplies the color of the texture, so a white texture can be drawn in any color
+	pub fn set_tint(&mut self, tint: RgbaColor) {
+		if self.tint.array() != tint.array() {
+			self.tint = tint;
+			self.touch();
+		}
+	}
+
+	pub fn tint(&self) -> RgbaColor {
+		self.tint
+	}
+
+	/// From 0 (invisible) to 1, on top of the alpha of the tint
+	pub fn set_opacity(&mut self, opacity: f32) {
+		let opacity = opacity.max(0.0).min(1.0);
+		if self.opacity != opacity {
+			self.opacity = opacity;
+			self.touch();
+		}
+	}
+
+	pub fn opacity(&self) -> f32 {
+		self.opacity
+	}
+
+	pub fn pos(&self) -> [i32; 2] {
+		self.pos
+	}
+
+	pub fn scale(&self) -> f32 {
+		self.scale
 	}
 
 	pub fn set_scale(&mut self, new_scale: f32) {
-		self.scale = new_scale
+		if self.scale != new_scale {
+			self.scale = new_scale;
+			self.touch();
+		}
 	}
 
-	pub fn set_depth(&mut self, new_depth: f32) {
-		self.depth = new_depth
+	/// Resizes a nine slice panel, the corners keep their size. Other entities can only be scaled
+	pub fn set_size(&mut self, size: [u32; 2]) -> Result<()> {
+		let nine_slice = match &mut self.panel {
+			Some((nine_slice, old_size)) => {
+				*old_size = size;
+				*nine_slice
+			}
+			None => {
+				return Err(Error::wrong_kind(
+					format!("entity {}", self.id),
+					"nine slice panel",
+				))
+			}
+		};
+		let pos = nine_slice
+			.grid_pos(size)
+			.into_iter()
+			.map(VertexPosition::new)
+			.collect::<Vec<_>>();
+		self.update_pos(&pos);
+		Ok(())
+	}
+
+	/// Size of a nine slice panel, `None` for other entities
+	pub fn size(&self) -> Option<[u32; 2]> {
+		self.panel.map(|(_, size)| size)
+	}
+
+	/// Layer the entity is drawn in, instead of the one of its parent (or `Layer::Ui` without one)
+	pub fn set_layer(&mut self, layer: Layer) {
+		if self.layer != Some(layer) {
+			self.layer = Some(layer);
+			self.touch();
+		}
 	}
 
-	pub fn get_depth(&self) -> f32 {
-		self.depth
+	/// Layer the entity is drawn in
+	pub fn layer(&self) -> Layer {
+		self.layer
+			.or_else(|| self.parent.map(|p| p.layer))
+			.unwrap_or_default()
 	}
 
 	pub fn state_ids(&self) -> Vec<&String> {
@@ -202,14 +535,119 @@ impl Entity {
 		}
 	}
 
-	pub fn set_state<S: ToString>(&mut self, id: S) -> Result<(), ()> {
+	pub fn set_state<S: ToString>(&mut self, id: S) -> Result<()> {
 		if let Some(uv_states) = self.uv_states.clone() {
 			if let Some(res) = uv_states.get(&id.to_string()) {
 				self.update_uv(&res);
+				self.state = Some(id.to_string());
 				return Ok(());
 			}
 		}
-		return Err(());
+		return Err(Error::unknown("state", id.to_string()));
+	}
+
+	pub fn animation_names(&self) -> Vec<&String> {
+		self.animations.keys().collect()
+	}
+
+	/// The animation being played, if there's one
+	pub fn animation(&self) -> Option<&AnimationPlayer> {
+		self.animation.as_ref()
+	}
+
+	/// Plays an animation from the start, replacing the current one
+	pub fn play_animation<S: ToString>(&mut self, name: S) -> Result<()> {
+		let name = name.to_string();
+		let player = AnimationPlayer::new(
+			&name,
+			self.animations
+				.get(&name)
+				.ok_or_else(|| Error::unknown("animation", &name))?,
+		);
+		let state = player.state().to_string();
+		self.animation = Some(player);
+		self.set_state(state)
+	}
+
+	pub fn pause_animation(&mut self) {
+		if let Some(player) = &mut self.animation {
+			player.pause()
+		}
+	}
+
+	pub fn resume_animation(&mut self) {
+		if let Some(player) = &mut self.animation {
+			player.play()
+		}
+	}
+
+	/// Stops the animation, keeping the current state
+	pub fn stop_animation(&mut self) {
+		self.animation = None
+	}
+
+	/// Jumps to a time of the current animation, see `AnimationPlayer::seek`
+	pub fn seek_animation(&mut self, time: f32) {
+		if let Some(player) = &mut self.animation {
+			player.seek(time);
+			let state = player.state().to_string();
+			let _ = self.set_state(state);
+		}
+	}
+
+	/// Advances the current animation and the tweens, returns the events of the frames that started,
+	/// `AnimationEvent::Finished` when an animation that doesn't loop gets to the end
+	/// and `AnimationEvent::TweenFinished` for the tweens that finished
+	pub fn update_animation(&mut self, dt: f32) -> Vec<AnimationEvent> {
+		let mut events = self.update_tweens(dt);
+		let state = match &mut self.animation {
+			Some(player) => {
+				events.extend(player.update(dt));
+				player.state().to_string()
+			}
+			None => return events,
+		};
+		if self.state.as_ref() != Some(&state) {
+			// The states were validated by `TextureData::load`
+			let _ = self.set_state(state);
+		}
+		events
+	}
+
+	/// Plays a tween on this entity, replacing the one with the same name.
+	/// It's advanced by `update_animation`, and removed when it finishes
+	pub fn play_tween<S: ToString>(&mut self, name: S, tween: Tween) {
+		let name = name.to_string();
+		self.stop_tween(&name);
+		self.tweens.push((name, tween));
+	}
+
+	/// Stops a tween where it is, returns whether it was playing
+	pub fn stop_tween(&mut self, name: &str) -> bool {
+		let len = self.tweens.len();
+		self.tweens.retain(|(n, _)| n != name);
+		self.tweens.len() != len
+	}
+
+	pub fn is_tweening(&self) -> bool {
+		!self.tweens.is_empty()
+	}
+
+	fn update_tweens(&mut self, dt: f32) -> Vec<AnimationEvent> {
+		let mut events = Vec::new();
+		// Taken out so the tweens can change the entity
+		let mut tweens = std::mem::take(&mut self.tweens);
+		let mut i = 0;
+		while i < tweens.len() {
+			if tweens[i].1.update(self, dt) {
+				let (name, _) = tweens.remove(i);
+				events.push(AnimationEvent::TweenFinished(name));
+			} else {
+				i += 1;
+			}
+		}
+		self.tweens = tweens;
+		events
 	}
 
 	pub fn update_uv(&mut self, new_uv: &[VertexUV]) {
@@ -218,43 +656,44 @@ impl Entity {
 			.as_slice_mut::<Vertex>()
 			.expect("Error getting mutablee slice");
 		for i in 0..v_slice.len() {
-			v_slice[i].update_uv(new_uv[i])
+			v_slice[i].update_uv(new_uv[i]);
+			self.vertices[i].update_uv(new_uv[i]);
 		}
+		self.touch();
 	}
 
 	pub fn update_text<T: ToString, C: GraphicsContext>(
 		&mut self,
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		text: &T,
 		font: &Font,
-	) -> Result<(), ()> {
+	) -> Result<()> {
 		//if let EntityKind::Text = self.kind {
-		if let Some((tex, uvs)) = tex_from_string(file_loader, surface, text.to_string(), font) {
-			let [width, height] = tex.size();
-			self.tex_size = tex.size();
-			self.tex = tex;
-			self.update(&[
-				(VertexPosition::new([0, 0]), VertexUV::new(uvs[0])),
-				(
-					VertexPosition::new([width as i32, 0]),
-					VertexUV::new(uvs[1]),
-				),
-				(
-					VertexPosition::new([width as i32, height as i32]),
-					VertexUV::new(uvs[2]),
-				),
-				(
-					VertexPosition::new([0, height as i32]),
-					VertexUV::new(uvs[3]),
-				),
-			]);
-			return Ok(());
-		} else {
-			eprintln!("Error creating texture for string \"{}\"", text.to_string())
-		}
+		let (tex, uvs) = tex_from_string(assets, file_loader, surface, text.to_string(), font)?;
+		let [width, height] = tex.size();
+		self.tex_size = tex.size();
+		self.tex = Handle::new(tex.into());
+		self.source = Some(Source::Text(text.to_string(), font.clone()));
+		self.dependencies = vec![FONTS_PATH.join(font.name())];
+		self.update(&[
+			(VertexPosition::new([0, 0]), VertexUV::new(uvs[0])),
+			(
+				VertexPosition::new([width as i32, 0]),
+				VertexUV::new(uvs[1]),
+			),
+			(
+				VertexPosition::new([width as i32, height as i32]),
+				VertexUV::new(uvs[2]),
+			),
+			(
+				VertexPosition::new([0, height as i32]),
+				VertexUV::new(uvs[3]),
+			),
+		]);
 		//}
-		Err(())
+		Ok(())
 	}
 
 	pub fn update_pos(&mut self, new_pos: &[VertexPosition]) {
@@ -263,8 +702,11 @@ impl Entity {
 			.as_slice_mut::<Vertex>()
 			.expect("Error getting mutablee slice");
 		for i in 0..v_slice.len() {
-			v_slice[i].update_pos(new_pos[i])
+			v_slice[i].update_pos(new_pos[i]);
+			self.vertices[i].update_pos(new_pos[i]);
 		}
+		self.extent = extent(new_pos.iter());
+		self.touch();
 	}
 
 	pub fn update(&mut self, new_v: &[(VertexPosition, VertexUV)]) {
@@ -275,12 +717,149 @@ impl Entity {
 		for i in 0..v_slice.len() {
 			v_slice[i].update_pos(new_v[i].0);
 			v_slice[i].update_uv(new_v[i].1);
+			self.vertices[i].update_pos(new_v[i].0);
+			self.vertices[i].update_uv(new_v[i].1);
 		}
+		self.extent = extent(new_v.iter().map(|(pos, _)| pos));
+		self.touch();
 	}
 
 	pub fn update_tex(&mut self, tex: Texture<Dim2, NormRGBA8UI>) {
+		self.update_tex_handle(Handle::new(tex.into()))
+	}
+
+	pub fn update_tex_handle(&mut self, tex: Handle<AnyTexture>) {
 		self.tex = tex;
 		self.tex_size = self.tex.size();
+		self.touch();
+	}
+
+	pub fn texture(&self) -> &Handle<AnyTexture> {
+		&self.tex
+	}
+
+	/// Unique for every entity
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	/// Changes every time the entity would be drawn differently
+	pub fn revision(&self) -> u64 {
+		self.revision
+	}
+
+	fn touch(&mut self) {
+		self.revision += 1
+	}
+
+	/// Adds the triangles of the entity to `out`, placed on a surface of this size like the HUD shaders do.
+	/// The `Batcher` draws them in a single call with the ones of other entities
+	pub fn batch_vertices(&self, surface_size: [u32; 2], out: &mut Vec<BatchVertex>) {
+		let (pos, scale) = self.placement(surface_size);
+		let pivot = self.pivot_point();
+		let (sin, cos) = self.rotation.sin_cos();
+		let tint = self.tint.float_array();
+		let color = [tint[0], tint[1], tint[2], tint[3] * self.opacity];
+		let clip = self.clip_array(surface_size);
+		for &i in &self.indices {
+			let vertex = &self.vertices[i as usize];
+			let (p, uv) = (vertex.get_pos(), vertex.get_uv());
+			let local = [p[0] as f32 - pivot[0], p[1] as f32 - pivot[1]];
+			let rotated = [
+				local[0] * cos - local[1] * sin + pivot[0],
+				local[0] * sin + local[1] * cos + pivot[1],
+			];
+			out.push(BatchVertex::new(
+				BatchPosition::new([
+					rotated[0] * scale + pos[0] as f32,
+					rotated[1] * scale + pos[1] as f32,
+				]),
+				BatchUV::new([
+					uv[0] as f32 / self.tex_size[0] as f32,
+					uv[1] as f32 / self.tex_size[1] as f32,
+				]),
+				BatchColor::new(color),
+				BatchClip::new(clip),
+			));
+		}
+	}
+
+	// The pivot in vertex positions
+	fn pivot_point(&self) -> [f32; 2] {
+		let fraction = self.pivot.fraction();
+		[
+			self.extent[0] as f32 + fraction[0] * (self.extent[2] - self.extent[0]) as f32,
+			self.extent[1] as f32 + fraction[1] * (self.extent[3] - self.extent[1]) as f32,
+		]
+	}
+
+	/// Whether the texture of this entity was built from the file at `p` (relative to the assets dir)
+	pub fn uses_file(&self, p: &Path) -> bool {
+		self.dependencies.iter().any(|d| d == p)
+	}
+
+	/// Rebuilds the texture (and UV states) from the files it was created from, keeping the current state
+	/// Invalidate the changed files in the `AssetServer` first, or the cached texture will be used.
+	/// Entities that weren't built from files are left untouched
+	pub fn reload<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+	) -> Result<()> {
+		match self.source.clone() {
+			Some(Source::TextureData(file)) => {
+				let tex_data = TextureData::load(file_loader, &file)?;
+				let img_path = TEXTURES_PATH.join(&tex_data.file);
+				let tex = assets.texture_with_settings(
+					file_loader,
+					surface,
+					&img_path,
+					&tex_data.settings,
+				)?;
+				self.update_tex_handle(tex);
+				// Panels stay panels, with the new borders if they're still there
+				if let Some((nine_slice, size)) = self.panel {
+					let nine_slice = tex_data.nine_slice.unwrap_or(nine_slice);
+					self.panel = Some((nine_slice, size));
+					let _ = self.set_size(size);
+				}
+				let nine_slice = self.panel.map(|(nine_slice, _)| nine_slice);
+				self.uv_states = Some(Self::uv_states_from(&tex_data, nine_slice.as_ref()));
+				self.dependencies = vec![img_path, TEXTURES_PATH.join(&file)];
+				// Keep playing the animation where it was, if it's still there
+				self.animation = match self.animation.take() {
+					Some(old) => tex_data.animations.get(old.name()).map(|animation| {
+						let mut player = AnimationPlayer::new(old.name(), animation);
+						player.seek(old.time());
+						if !old.is_playing() {
+							player.pause()
+						}
+						player
+					}),
+					None => None,
+				};
+				self.animations = tex_data.animations.clone();
+				let state = self
+					.state
+					.clone()
+					.filter(|s| tex_data.uv.contains_key(s))
+					.unwrap_or(tex_data.default_uv);
+				// The animation decides the state, if there's one
+				let state = self
+					.animation
+					.as_ref()
+					.map(|player| player.state().to_string())
+					.unwrap_or(state);
+				// Both were validated by `TextureData::load`
+				let _ = self.set_state(state);
+				Ok(())
+			}
+			Some(Source::Text(text, font)) => {
+				self.update_text(assets, file_loader, surface, &text, &font)
+			}
+			None => Ok(()),
+		}
 	}
 
 	pub fn render<C: GraphicsContext>(
@@ -290,15 +869,67 @@ impl Entity {
 		tess_gate: &mut TessGate<C>,
 		size: &[u32; 2],
 	) {
-		let bound_tex = pipeline.bind_texture(&self.tex);
-
-		iface.tex.update(&bound_tex);
+		let (pos, scale) = self.placement(*size);
 		iface.size.update(size.clone().into());
-		iface.pos.update(self.pos.into());
-		iface.depth.update(self.depth.into());
-		iface.scale.update(self.scale.into());
+		iface.pos.update(pos.into());
+		iface.scale.update(scale.into());
 		iface.tex_size.update(self.tex_size.into());
+		iface.rotation.update(self.rotation);
+		iface.pivot.update(self.pivot_point());
+		iface.tint.update(self.tint.float_array());
+		iface.opacity.update(self.opacity);
+		iface.clip.update(self.clip_array(*size));
+		iface.gray_tex.update(match &*self.tex {
+			AnyTexture::R8(_) | AnyTexture::R16(_) => true,
+			_ => false,
+		});
+		iface.srgb_tex.update(match &*self.tex {
+			AnyTexture::Srgba8(_) => true,
+			_ => false,
+		});
 
+		// Every format but the floating point one is bound to the same sampler
+		match &*self.tex {
+			AnyTexture::Rgba8(tex) => self.render_norm(pipeline, iface, tess_gate, tex),
+			AnyTexture::Srgba8(tex) => self.render_norm(pipeline, iface, tess_gate, tex),
+			AnyTexture::R8(tex) => self.render_norm(pipeline, iface, tess_gate, tex),
+			AnyTexture::R16(tex) => self.render_norm(pipeline, iface, tess_gate, tex),
+			AnyTexture::Rgba16(tex) => self.render_norm(pipeline, iface, tess_gate, tex),
+			AnyTexture::RgbF32(tex) => {
+				let bound_tex = pipeline.bind_texture(tex);
+				iface.tex_floating.update(&bound_tex);
+				iface.floating_tex.update(true);
+				tess_gate.render(self.vao.slice(..));
+			}
+		}
+	}
+
+	fn render_norm<C: GraphicsContext, P: Pixel<SamplerType = NormUnsigned>>(
+		&self,
+		pipeline: &Pipeline,
+		iface: &ProgramInterface<'_, HudUniformInterface>,
+		tess_gate: &mut TessGate<C>,
+		tex: &Texture<Dim2, P>,
+	) {
+		let bound_tex = pipeline.bind_texture(tex);
+		iface.tex.update(&bound_tex);
+		iface.floating_tex.update(false);
 		tess_gate.render(self.vao.slice(..));
 	}
 }
+
+// Bounding box of the vertex positions: min x, min y, max x, max y
+fn extent<'v, I: Iterator<Item = &'v VertexPosition>>(positions: I) -> [i32; 4] {
+	let mut extent = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+	for pos in positions {
+		extent[0] = extent[0].min(pos[0]);
+		extent[1] = extent[1].min(pos[1]);
+		extent[2] = extent[2].max(pos[0]);
+		extent[3] = extent[3].max(pos[1]);
+	}
+	if extent[0] > extent[2] {
+		[0; 4] // No vertices
+	} else {
+		extent
+	}
+}
@@ -0,0 +1,41 @@
+use super::Entity;
+
+/// Group of HUD entities, drawn from the first to the last. Inside a layer, entities are drawn in the order
+/// they were added (to the `Scene`, `EntityRegistry` or list given to the renderer)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+	Background,
+	WorldOverlay, // Markers and labels that follow things in the 3D world
+	Ui,
+	Tooltip,
+	Debug,
+}
+
+impl Default for Layer {
+	fn default() -> Self {
+		Self::Ui
+	}
+}
+
+#[allow(dead_code)]
+impl Layer {
+	/// Every layer, in drawing order
+	pub const ALL: [Layer; 5] = [
+		Layer::Background,
+		Layer::WorldOverlay,
+		Layer::Ui,
+		Layer::Tooltip,
+		Layer::Debug,
+	];
+}
+
+/// Puts the entities in drawing order: by layer, keeping their order inside each one.
+/// Nothing is compared, so it's linear
+pub fn layer_order<'e, I: IntoIterator<Item = &'e Entity>>(entities: I) -> Vec<&'e Entity> {
+	let mut layers: [Vec<&Entity>; Layer::ALL.len()] = Default::default();
+	for entity in entities {
+		layers[entity.layer() as usize].push(entity);
+	}
+	layers.concat()
+}
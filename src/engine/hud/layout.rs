@@ -0,0 +1,135 @@
+/// Point of the surface (anchor) or of an entity (pivot)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+	TopLeft,
+	Top,
+	TopRight,
+	Left,
+	Center,
+	Right,
+	BottomLeft,
+	Bottom,
+	BottomRight,
+	Fraction(f32, f32), // Of the width and height, from the bottom left like positions
+}
+
+impl Anchor {
+	/// Fraction of the width and height, from the bottom left
+	pub fn fraction(&self) -> [f32; 2] {
+		match *self {
+			Self::TopLeft => [0.0, 1.0],
+			Self::Top => [0.5, 1.0],
+			Self::TopRight => [1.0, 1.0],
+			Self::Left => [0.0, 0.5],
+			Self::Center => [0.5, 0.5],
+			Self::Right => [1.0, 0.5],
+			Self::BottomLeft => [0.0, 0.0],
+			Self::Bottom => [0.5, 0.0],
+			Self::BottomRight => [1.0, 0.0],
+			Self::Fraction(x, y) => [x, y],
+		}
+	}
+}
+
+/// Distance along one axis, right or up when positive
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Offset {
+	Pixels(i32),  // Scaled with the entity if the layout has a reference height
+	Percent(f32), // Of the surface width or height
+}
+
+/// Where an entity goes on the surface: its pivot is put on the anchor, then moved by the offset.
+/// It's recomputed every time the entity is rendered, so it follows the size of the surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+	pub anchor: Anchor,
+	pub pivot: Anchor,
+	pub offset: [Offset; 2],
+	pub reference_height: Option<u32>, // Surface height at which the entity has its own scale
+}
+
+#[allow(dead_code)]
+impl Layout {
+	/// The pivot is the same point as the anchor, so the entity stays inside the surface
+	pub fn new(anchor: Anchor) -> Self {
+		Self {
+			anchor,
+			pivot: anchor,
+			offset: [Offset::Pixels(0); 2],
+			reference_height: None,
+		}
+	}
+
+	pub fn with_pivot(mut self, pivot: Anchor) -> Self {
+		self.pivot = pivot;
+		self
+	}
+
+	pub fn with_offset(mut self, x: Offset, y: Offset) -> Self {
+		self.offset = [x, y];
+		self
+	}
+
+	/// Scales the entity (and its pixel offsets) with the height of the surface,
+	/// so it covers the same part of it at any resolution
+	pub fn with_reference_height(mut self, height: u32) -> Self {
+		self.reference_height = Some(height);
+		self
+	}
+
+	/// How much the entity is scaled on a surface of this size, on top of its own scale
+	pub fn scale(&self, surface_size: [u32; 2]) -> f32 {
+		match self.reference_height {
+			Some(height) if height > 0 => surface_size[1] as f32 / height as f32,
+			_ => 1.0,
+		}
+	}
+
+	/// Position of an entity whose scaled vertices go from `min` to `max`, relative to its position
+	pub fn position(&self, surface_size: [u32; 2], min: [f32; 2], max: [f32; 2]) -> [i32; 2] {
+		self.position_in(surface_size, self.scale(surface_size), min, max)
+	}
+
+	/// Like `position`, in a rectangle of `size` pixels (like a parent entity) whose pixel offsets
+	/// are scaled by `scale`. It's relative to the bottom left of the rectangle
+	pub fn position_in(
+		&self,
+		size: [u32; 2],
+		scale: f32,
+		min: [f32; 2],
+		max: [f32; 2],
+	) -> [i32; 2] {
+		let anchor = self.anchor.fraction();
+		let pivot = self.pivot.fraction();
+		let mut pos = [0; 2];
+		for i in 0..2 {
+			let offset = match self.offset[i] {
+				Offset::Pixels(p) => p as f32 * scale,
+				Offset::Percent(p) => p / 100.0 * size[i] as f32,
+			};
+			let target = anchor[i] * size[i] as f32 + offset;
+			pos[i] = (target - (min[i] + pivot[i] * (max[i] - min[i]))).round() as i32;
+		}
+		pos
+	}
+}
+
+/// Part of the surface both clip rectangles (min x, min y, max x, max y) cover, `None` means no clipping.
+/// Rectangles that don't overlap give an empty one
+pub fn intersect_clip(a: Option<[i32; 4]>, b: Option<[i32; 4]>) -> Option<[i32; 4]> {
+	match (a, b) {
+		(Some(a), Some(b)) => {
+			let (min_x, min_y) = (a[0].max(b[0]), a[1].max(b[1]));
+			Some([
+				min_x,
+				min_y,
+				a[2].min(b[2]).max(min_x),
+				a[3].min(b[3]).max(min_y),
+			])
+		}
+		(a, None) => a,
+		(None, b) => b,
+	}
+}
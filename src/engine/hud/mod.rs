@@ -5,18 +5,33 @@ use luminance::texture::Dim2;
 
 use luminance_derive::{Semantics, UniformInterface, Vertex};
 
+mod animation;
+mod batch;
 mod depth_entity;
 mod entity;
+mod layer;
+mod layout;
+mod overlay;
 mod renderer;
+mod scene;
+mod tween;
+mod widget;
 
+pub use animation::{AnimationEvent, AnimationPlayer};
+pub use batch::Batcher;
 pub use depth_entity::Entity as DepthEntity;
 pub use entity::{Entity, EntityKind};
+pub use layer::Layer;
+pub use layout::{Anchor, Layout, Offset};
+pub use overlay::PerfOverlay;
 pub use renderer::Renderer;
+pub use scene::{Parent, Scene};
+pub use tween::{Easing, Target, Tween};
+pub use widget::{Theme, Ui, UiEvent, Widget, WidgetKind};
 
 #[derive(UniformInterface)]
 pub struct HudUniformInterface {
 	pos: Uniform<[i32; 2]>,
-	depth: Uniform<f32>,
 	scale: Uniform<f32>,
 	size: Uniform<[u32; 2]>,
 	#[uniform(unbound)]
@@ -24,7 +39,15 @@ pub struct HudUniformInterface {
 	#[uniform(unbound)]
 	tex_floating: Uniform<&'static BoundTexture<'static, Dim2, Floating>>,
 	tex_size: Uniform<[u32; 2]>,
+	rotation: Uniform<f32>,
+	pivot: Uniform<[f32; 2]>,
+	tint: Uniform<[f32; 4]>,
+	opacity: Uniform<f32>,
+	clip: Uniform<[f32; 4]>, // Min x, min y, max x, max y on the surface
 	depth_tex: Uniform<bool>,
+	floating_tex: Uniform<bool>,
+	gray_tex: Uniform<bool>,
+	srgb_tex: Uniform<bool>,
 }
 
 #[derive(Copy, Clone, Debug, Semantics)]
@@ -0,0 +1,292 @@
+use luminance::context::GraphicsContext;
+
+use std::path::Path;
+
+use super::layer::Layer;
+use super::layout::{Anchor, Layout, Offset};
+use super::widget::quad_pos;
+use super::{Entity, Vertex, VertexPosition, VertexUV};
+
+use super::super::assets::{AssetServer, AssetStats, Handle};
+use super::super::error::Result;
+use super::super::stats::{FrameTimer, RenderStats};
+use super::super::text::{Font, FontStyle, FontWeight};
+use super::super::texture::{AnyTexture, TextureSettings};
+use super::super::utils::load_with_settings;
+use super::super::{FileLoader, RgbaColor};
+
+// Seconds between updates of the text, so it can be read
+const REFRESH: f32 = 0.25;
+
+// In pixels, from the top left corner of the surface
+const MARGIN: i32 = 10;
+const PADDING: i32 = 8;
+const LINE_HEIGHT: i32 = 20;
+
+// One bar per frame, the newest on the right. Indices are bytes, so it's at most 64 quads
+const GRAPH_BARS: usize = 60;
+const BAR_WIDTH: i32 = 4;
+const GRAPH_HEIGHT: i32 = 60;
+const GRAPH_MS: f32 = 1000.0 / 30.0; // Frame time at the top of the graph
+const TARGET_MS: f32 = 1000.0 / 60.0;
+
+/// Debug overlay in the top left corner with the frame rate, a graph of the last frame times,
+/// what every renderer drew and the asset cache. It's drawn in the `Debug` layer
+pub struct PerfOverlay {
+	font: Font,
+	visible: bool,
+	since_refresh: f32,
+	background: Entity,
+	graph: Entity,
+	target: Entity,               // Line at 60 FPS
+	lines: Vec<(String, Entity)>, // Text entities can't have several lines
+}
+
+#[allow(dead_code)]
+impl PerfOverlay {
+	pub fn new<C: GraphicsContext>(surface: &mut C) -> Result<Self> {
+		// A white pixel, every part of the overlay is a tinted quad of it
+		let img = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+		let white: Handle<AnyTexture> =
+			Handle::new(load_with_settings(surface, img, &TextureSettings::pixel_art())?.into());
+
+		let mut background = quad(surface, &white, [1, 1])?;
+		background.set_tint(RgbaColor::new(0, 0, 0, 180));
+		let mut target = quad(surface, &white, [GRAPH_BARS as u32 * BAR_WIDTH as u32, 1])?;
+		target.set_tint(RgbaColor::new(250, 60, 60, 255));
+
+		// Every bar is there from the start, without height
+		let mut vertices = Vec::with_capacity(GRAPH_BARS * 4);
+		let mut indices = Vec::with_capacity(GRAPH_BARS * 6);
+		for i in 0..GRAPH_BARS {
+			let first = vertices.len() as u8;
+			let uv = quad_uv();
+			vertices.extend(
+				bar_pos(i, 0.0)
+					.iter()
+					.zip(uv.iter())
+					.map(|(pos, uv)| Vertex::new(*pos, *uv)),
+			);
+			indices.extend([0, 1, 2, 0, 2, 3].iter().map(|v| first + v));
+		}
+		let mut graph = Entity::new_from_handle(surface, &vertices, &indices, white)?;
+		graph.set_tint(RgbaColor::new(90, 220, 120, 255));
+
+		let mut overlay = Self {
+			font: Font::new("Roboto", FontWeight::Regular, FontStyle::Regular, 16.0),
+			visible: false,
+			since_refresh: REFRESH,
+			background,
+			graph,
+			target,
+			lines: Vec::new(),
+		};
+		for entity in overlay.entities_mut() {
+			entity.set_layer(Layer::Debug);
+		}
+		Ok(overlay)
+	}
+
+	pub fn toggle(&mut self) {
+		self.set_visible(!self.visible)
+	}
+
+	pub fn set_visible(&mut self, visible: bool) {
+		if visible && !self.visible {
+			// Shows the current numbers right away
+			self.since_refresh = REFRESH;
+		}
+		self.visible = visible
+	}
+
+	pub fn is_visible(&self) -> bool {
+		self.visible
+	}
+
+	/// Call it every frame after ticking the timer, with the stats taken from the renderers
+	/// (by name) on the last frame. Does nothing while it's hidden
+	pub fn update<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		timer: &FrameTimer,
+		renderers: &[(&str, RenderStats)],
+		asset_stats: AssetStats,
+	) -> Result<()> {
+		if !self.visible {
+			return Ok(());
+		}
+		self.update_graph(timer);
+
+		self.since_refresh += timer.last();
+		if self.since_refresh < REFRESH {
+			return Ok(());
+		}
+		self.since_refresh = 0.0;
+
+		let mut texts = vec![format!(
+			"{:.0} FPS, {:.1} ms average, {:.1} ms max",
+			timer.fps(),
+			timer.average() * 1000.0,
+			timer.max() * 1000.0
+		)];
+		for (name, stats) in renderers {
+			texts.push(format!(
+				"{}: {} draw calls, {} entities, {} uploads",
+				name, stats.draw_calls, stats.entities, stats.uploads
+			));
+		}
+		texts.push(format!(
+			"Assets: {} ({} unused), {} textures ({:.1} MB)",
+			asset_stats.assets,
+			asset_stats.unused,
+			asset_stats.textures,
+			asset_stats.texture_bytes as f32 / (1024.0 * 1024.0)
+		));
+
+		self.lines.truncate(texts.len());
+		for (i, text) in texts.into_iter().enumerate() {
+			match self.lines.get_mut(i) {
+				Some((old, _)) if *old == text => (),
+				Some((old, entity)) => {
+					entity.update_text(assets, file_loader, surface, &text, &self.font)?;
+					*old = text;
+				}
+				None => {
+					let mut entity = Entity::new_entity_from_string(
+						assets,
+						file_loader,
+						surface,
+						text.clone(),
+						&self.font,
+					)?;
+					entity.set_layer(Layer::Debug);
+					self.lines.push((text, entity));
+				}
+			}
+		}
+		self.arrange();
+		Ok(())
+	}
+
+	/// Rebuilds the text when the font at `path` (relative to the assets dir) changed on disk
+	pub fn reload<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		path: &Path,
+	) -> Result<()> {
+		let mut reloaded = false;
+		for (_, entity) in &mut self.lines {
+			if entity.uses_file(path) {
+				entity.reload(assets, file_loader, surface)?;
+				reloaded = true;
+			}
+		}
+		if reloaded {
+			self.arrange();
+		}
+		Ok(())
+	}
+
+	fn update_graph(&mut self, timer: &FrameTimer) {
+		let times = timer.frame_times().collect::<Vec<_>>();
+		let times = &times[times.len().saturating_sub(GRAPH_BARS)..];
+		let empty = GRAPH_BARS - times.len();
+		let mut pos = Vec::with_capacity(GRAPH_BARS * 4);
+		for i in 0..GRAPH_BARS {
+			let ms = if i < empty {
+				0.0
+			} else {
+				times[i - empty] * 1000.0
+			};
+			pos.extend_from_slice(&bar_pos(i, ms));
+		}
+		self.graph.update_pos(&pos);
+	}
+
+	// Places the text lines, then the graph below them, with the background behind everything
+	fn arrange(&mut self) {
+		let mut width = GRAPH_BARS as i32 * BAR_WIDTH;
+		let mut bottom = MARGIN + PADDING;
+		for (_, entity) in &mut self.lines {
+			let [min_x, _, max_x, _] = entity.extent();
+			width = width.max(max_x - min_x);
+			bottom += LINE_HEIGHT;
+			place(entity, [MARGIN + PADDING, bottom]);
+		}
+		bottom += PADDING + GRAPH_HEIGHT;
+		place(&mut self.graph, [MARGIN + PADDING, bottom]);
+		let target = (TARGET_MS / GRAPH_MS * GRAPH_HEIGHT as f32).round() as i32;
+		place(&mut self.target, [MARGIN + PADDING, bottom - target]);
+
+		bottom += PADDING;
+		let size = [width + 2 * PADDING, bottom - MARGIN];
+		self.background
+			.update_pos(&quad_pos([size[0] as u32, size[1] as u32]));
+		place(&mut self.background, [MARGIN, bottom]);
+	}
+
+	fn entities_mut(&mut self) -> Vec<&mut Entity> {
+		let mut entities = vec![&mut self.background, &mut self.graph, &mut self.target];
+		entities.extend(self.lines.iter_mut().map(|(_, e)| e));
+		entities
+	}
+
+	/// Entities to draw, none while it's hidden. The ones without text share a texture
+	pub fn entities(&self) -> Vec<&Entity> {
+		if !self.visible {
+			return Vec::new();
+		}
+		let mut entities = vec![&self.background, &self.graph, &self.target];
+		entities.extend(self.lines.iter().map(|(_, e)| e));
+		entities
+	}
+}
+
+// Puts the bottom left of an entity `pos` pixels right of and below the top left of the surface
+fn place(entity: &mut Entity, pos: [i32; 2]) {
+	entity.set_layout(
+		Layout::new(Anchor::TopLeft)
+			.with_pivot(Anchor::BottomLeft)
+			.with_offset(Offset::Pixels(pos[0]), Offset::Pixels(-pos[1])),
+	);
+}
+
+fn quad<C: GraphicsContext>(
+	surface: &mut C,
+	tex: &Handle<AnyTexture>,
+	size: [u32; 2],
+) -> Result<Entity> {
+	let uv = quad_uv();
+	let vertices = quad_pos(size)
+		.iter()
+		.zip(uv.iter())
+		.map(|(pos, uv)| Vertex::new(*pos, *uv))
+		.collect::<Vec<_>>();
+	Entity::new_from_handle(surface, &vertices, &[0, 1, 2, 0, 2, 3], tex.clone())
+}
+
+// The whole texture, in the same order as `quad_pos`
+fn quad_uv() -> [VertexUV; 4] {
+	[
+		VertexUV::new([0, 0]),
+		VertexUV::new([1, 0]),
+		VertexUV::new([1, 1]),
+		VertexUV::new([0, 1]),
+	]
+}
+
+// Bar `i` of the graph for a frame of `ms` milliseconds, capped at the top of the graph
+fn bar_pos(i: usize, ms: f32) -> [VertexPosition; 4] {
+	let x = i as i32 * BAR_WIDTH;
+	let h = (ms.min(GRAPH_MS) / GRAPH_MS * GRAPH_HEIGHT as f32).round() as i32;
+	[
+		VertexPosition::new([x, 0]),
+		VertexPosition::new([x + BAR_WIDTH - 1, 0]),
+		VertexPosition::new([x + BAR_WIDTH - 1, h]),
+		VertexPosition::new([x, h]),
+	]
+}
@@ -6,9 +6,11 @@ use luminance::render_state::RenderState;
 use luminance::shader::program::Program;
 use luminance::texture::{Dim2, Texture};
 
-use std::cmp::Ordering;
+use std::cell::Cell;
 
+use super::super::stats::RenderStats;
 use super::super::EntityRegistry;
+use super::layer::layer_order;
 use super::{DepthEntity, Entity, HudUniformInterface, VertexSemantics};
 
 const VS_STR: &str = include_str!("shaders/vs.glsl");
@@ -17,6 +19,7 @@ const FS_STR: &str = include_str!("shaders/fs.glsl");
 pub struct Renderer {
 	program: Program<VertexSemantics, (), HudUniformInterface>,
 	render_st: RenderState,
+	stats: Cell<RenderStats>,
 }
 
 impl Renderer {
@@ -25,12 +28,20 @@ impl Renderer {
 			Program::from_strings(None, VS_STR, None, FS_STR)
 				.expect("Error loading HUD shaders")
 				.ignore_warnings();
-		let render_st = RenderState::default().set_blending((
-			Equation::Additive,
-			Factor::SrcAlpha,
-			Factor::SrcAlphaComplement,
-		));
-		Self { program, render_st }
+		// Entities are drawn in order (see `Layer`), the depth buffer would hide the ones drawn later
+		let render_st = RenderState::default()
+			.set_blending((
+				Equation::Additive,
+				Factor::SrcAlpha,
+				Factor::SrcAlphaComplement,
+			))
+			.set_depth_test(None);
+		// The render state can't scissor, the shaders discard what's outside of the clip rectangle of each entity
+		Self {
+			program,
+			render_st,
+			stats: Cell::new(RenderStats::default()),
+		}
 	}
 
 	pub fn render<C: GraphicsContext>(
@@ -39,20 +50,36 @@ impl Renderer {
 		shd_gate: &mut ShadingGate<'_, C>,
 		pipeline: &Pipeline,
 		size: &[u32; 2],
-		d: &DepthEntity,
-		d_tex: &Texture<Dim2, Depth32F>,
+		depth: Option<(&DepthEntity, &Texture<Dim2, Depth32F>)>,
 	) {
-		let mut ordered = registry.values();
-		ordered.sort_by(|x, y| {
-			x.get_depth()
-				.partial_cmp(&y.get_depth())
-				.unwrap_or(Ordering::Equal)
-				.reverse()
-		});
+		self.render_entities(registry.values(), shd_gate, pipeline, size, depth)
+	}
+
+	/// Renders entities that aren't in a registry, like the ones of the widgets
+	pub fn render_entities<C: GraphicsContext>(
+		&self,
+		entities: Vec<&Entity>,
+		shd_gate: &mut ShadingGate<'_, C>,
+		pipeline: &Pipeline,
+		size: &[u32; 2],
+		depth: Option<(&DepthEntity, &Texture<Dim2, Depth32F>)>,
+	) {
+		let ordered = layer_order(entities.into_iter().filter(|e| e.is_visible()));
+		// Every entity is a draw call
+		self.stats.set(
+			self.stats.get()
+				+ RenderStats {
+					draw_calls: ordered.len() + depth.is_some() as usize,
+					entities: ordered.len(),
+					uploads: 0,
+				},
+		);
 		shd_gate.shade(&self.program, |iface, mut rdr_gate| {
 			rdr_gate.render(&self.render_st, |mut tess_gate| {
-				iface.depth_tex.update(true);
-				d.render(&pipeline, &iface, &mut tess_gate, &size, d_tex);
+				if let Some((d, d_tex)) = depth {
+					iface.depth_tex.update(true);
+					d.render(&pipeline, &iface, &mut tess_gate, &size, d_tex);
+				}
 				for e in ordered {
 					iface.depth_tex.update(false);
 					e.render(&pipeline, &iface, &mut tess_gate, &size);
@@ -60,4 +87,9 @@ impl Renderer {
 			})
 		});
 	}
+
+	/// What was drawn since the last call
+	pub fn take_stats(&self) -> RenderStats {
+		self.stats.take()
+	}
 }
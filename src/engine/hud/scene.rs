@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use super::layout::intersect_clip;
+use super::{Entity, Layer};
+
+use super::super::error::{Error, Result};
+
+/// Placement of the parent of an entity, which its own is relative to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parent {
+	pub origin: [i32; 2], // Bottom left of the parent
+	pub size: [u32; 2],
+	pub scale: f32,
+	pub layer: Layer,
+	pub visible: bool,
+	pub clip: Option<[i32; 4]>, // For the children, from the parent and the entities above it
+}
+
+struct Node {
+	entity: Entity,
+	parent: Option<String>,
+	children: Vec<String>, // In the order they were added
+}
+
+/// Named HUD entities in a tree, like an `EntityRegistry` where entities can have parents.
+/// Children are placed relative to their parent, and inherit its scale, layer, visibility and clip rectangle,
+/// so moving or hiding a panel takes its icons and text with it
+pub struct Scene {
+	nodes: HashMap<String, Node>,
+	roots: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Scene {
+	pub fn new() -> Self {
+		Self {
+			nodes: HashMap::new(),
+			roots: Vec::new(),
+		}
+	}
+
+	/// Adds an entity without a parent, replacing the one with the same name (and its children)
+	pub fn add<T: ToString>(&mut self, name: &T, entity: Entity) {
+		let name = name.to_string();
+		self.remove(&name);
+		self.roots.push(name.clone());
+		self.nodes.insert(
+			name,
+			Node {
+				entity,
+				parent: None,
+				children: Vec::new(),
+			},
+		);
+	}
+
+	/// Adds an entity as the last child of `parent`, which has to be in the scene.
+	/// In the same layer, children are drawn after their parent
+	pub fn add_child<T: ToString, P: ToString>(
+		&mut self,
+		parent: &P,
+		name: &T,
+		entity: Entity,
+	) -> Result<()> {
+		let (parent, name) = (parent.to_string(), name.to_string());
+		if !self.nodes.contains_key(&parent) {
+			return Err(Error::unknown("entity", parent));
+		}
+		// Before adding it, replacing an entity with the same name also removes the parent if it is below it
+		self.check_parent(&name, &parent)?;
+		self.add(&name, entity);
+		self.set_parent(&name, Some(&parent))
+	}
+
+	/// Moves an entity (and its children) under another parent, or to the root with `None`.
+	/// Fails if the entity or the parent don't exist, or the parent is one of its descendants
+	pub fn set_parent<T: ToString, P: ToString>(
+		&mut self,
+		name: &T,
+		parent: Option<&P>,
+	) -> Result<()> {
+		let name = name.to_string();
+		let parent = parent.map(|p| p.to_string());
+		if !self.nodes.contains_key(&name) {
+			return Err(Error::unknown("entity", name));
+		}
+		if let Some(parent) = &parent {
+			self.check_parent(&name, parent)?;
+		}
+		self.detach(&name);
+		match &parent {
+			Some(parent) => self
+				.nodes
+				.get_mut(parent)
+				.unwrap()
+				.children
+				.push(name.clone()),
+			None => self.roots.push(name.clone()),
+		}
+		let node = self.nodes.get_mut(&name).unwrap();
+		node.parent = parent;
+		if node.parent.is_none() {
+			node.entity.set_parent(None);
+		}
+		Ok(())
+	}
+
+	// Walks up from the new parent, the entity can't be above itself
+	fn check_parent(&self, name: &str, parent: &str) -> Result<()> {
+		let mut ancestor = Some(parent.to_string());
+		while let Some(a) = ancestor {
+			if a == name {
+				return Err(Error::Cycle {
+					name: name.to_string(),
+					parent: parent.to_string(),
+				});
+			}
+			ancestor = self
+				.nodes
+				.get(&a)
+				.ok_or_else(|| Error::unknown("entity", &a))?
+				.parent
+				.clone();
+		}
+		Ok(())
+	}
+
+	// Removes the entity from the children of its parent, or the roots
+	fn detach(&mut self, name: &str) {
+		let parent = self.nodes.get(name).and_then(|n| n.parent.clone());
+		let siblings = match parent {
+			Some(parent) => &mut self.nodes.get_mut(&parent).unwrap().children,
+			None => &mut self.roots,
+		};
+		siblings.retain(|n| n != name);
+	}
+
+	/// Removes an entity and its children, returns the entity
+	pub fn remove<T: ToString>(&mut self, name: &T) -> Option<Entity> {
+		let name = name.to_string();
+		if !self.nodes.contains_key(&name) {
+			return None;
+		}
+		self.detach(&name);
+		let node = self.nodes.remove(&name).unwrap();
+		let mut orphans = node.children;
+		while let Some(orphan) = orphans.pop() {
+			if let Some(child) = self.nodes.remove(&orphan) {
+				orphans.extend(child.children);
+			}
+		}
+		Some(node.entity)
+	}
+
+	pub fn get<T: ToString>(&self, name: &T) -> Option<&Entity> {
+		self.nodes.get(&name.to_string()).map(|n| &n.entity)
+	}
+
+	pub fn get_mut<T: ToString>(&mut self, name: &T) -> Option<&mut Entity> {
+		self.nodes.get_mut(&name.to_string()).map(|n| &mut n.entity)
+	}
+
+	pub fn parent<T: ToString>(&self, name: &T) -> Option<&str> {
+		self.nodes.get(&name.to_string())?.parent.as_deref()
+	}
+
+	pub fn children<T: ToString>(&self, name: &T) -> Vec<&str> {
+		match self.nodes.get(&name.to_string()) {
+			Some(node) => node.children.iter().map(|c| c.as_str()).collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Every entity, parents before their children and siblings in the order they were added.
+	/// Hidden ones are skipped by the renderers
+	pub fn values(&self) -> Vec<&Entity> {
+		self.order()
+			.iter()
+			.map(|name| &self.nodes[name].entity)
+			.collect()
+	}
+
+	/// Every entity, in the same order as `values`
+	pub fn values_mut(&mut self) -> Vec<&mut Entity> {
+		let order = self.order();
+		let mut entities = self
+			.nodes
+			.iter_mut()
+			.map(|(name, node)| (name.as_str(), &mut node.entity))
+			.collect::<HashMap<_, _>>();
+		order
+			.iter()
+			.filter_map(|name| entities.remove(name.as_str()))
+			.collect()
+	}
+
+	// Names of the entities, parents before their children and siblings in the order they were added
+	fn order(&self) -> Vec<String> {
+		let mut order = Vec::with_capacity(self.nodes.len());
+		let mut stack = self.roots.iter().rev().collect::<Vec<_>>();
+		while let Some(name) = stack.pop() {
+			order.push(name.clone());
+			stack.extend(self.nodes[name].children.iter().rev());
+		}
+		order
+	}
+
+	/// Places the children relative to their parents on a surface of this size.
+	/// Call it after moving, scaling or hiding entities, before they're drawn (every frame is fine)
+	pub fn update(&mut self, surface_size: [u32; 2]) {
+		let mut stack = self.roots.iter().rev().cloned().collect::<Vec<_>>();
+		while let Some(name) = stack.pop() {
+			let node = &self.nodes[&name];
+			let entity = &node.entity;
+			let rect = entity.rect(surface_size);
+			let placement = Parent {
+				origin: [rect[0], rect[1]],
+				size: [(rect[2] - rect[0]) as u32, (rect[3] - rect[1]) as u32],
+				scale: entity.placement(surface_size).1,
+				layer: entity.layer(),
+				visible: entity.is_visible(),
+				clip: if entity.clips_children() {
+					intersect_clip(entity.clip(), Some(rect))
+				} else {
+					entity.clip()
+				},
+			};
+			let children = node.children.clone();
+			for child in &children {
+				self.nodes
+					.get_mut(child)
+					.unwrap()
+					.entity
+					.set_parent(Some(placement));
+			}
+			stack.extend(children.into_iter().rev());
+		}
+	}
+}
+
+impl std::fmt::Debug for Scene {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		write!(f, "{:?}", self.roots)
+	}
+}
@@ -0,0 +1,38 @@
+// #version 150
+
+in vec2 v_uv;
+in vec4 v_color; // Tint & opacity of the entity
+flat in vec4 v_clip; // Min x, min y, max x, max y on the surface
+
+out vec4 frag;
+
+uniform sampler2D tex;
+uniform sampler2D tex_floating;
+
+uniform bool floating_tex; // HDR images, drawn without tone mapping
+uniform bool gray_tex; // Single channel textures, drawn in grayscale instead of red
+uniform bool srgb_tex; // sRGB textures, decoded to linear when they're sampled
+
+// The framebuffer isn't sRGB, so the colors have to be written encoded
+vec3 linear_to_srgb(vec3 c) {
+	return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), c));
+}
+
+void main(){
+	// Scissor test of the entity, pixels outside of its clip rectangle aren't drawn
+	if (gl_FragCoord.x < v_clip.x || gl_FragCoord.y < v_clip.y || gl_FragCoord.x > v_clip.z || gl_FragCoord.y > v_clip.w) {
+		discard;
+	}
+	if (floating_tex) {
+		frag = vec4(texture(tex_floating,v_uv).rgb, 1.0);
+	} else if (gray_tex) {
+		float v = texture(tex,v_uv).r;
+		frag = vec4(vec3(v), 1.0);
+	} else {
+		frag = texture(tex,v_uv);
+		if (srgb_tex) {
+			frag.rgb = linear_to_srgb(frag.rgb);
+		}
+	}
+	frag = frag * v_color;
+}
@@ -0,0 +1,22 @@
+// #version 150
+
+// Vertices of several entities, already placed on the surface
+in vec2 position; // Pixels
+in vec2 uv;
+in vec4 color;
+in vec4 clip;
+
+out vec2 v_uv;
+out vec4 v_color;
+flat out vec4 v_clip;
+
+uniform uvec2 size;
+
+
+void main(){
+	v_uv = uv;
+	v_color = color;
+	v_clip = clip;
+
+	gl_Position = vec4(position.x*2./float(size[0]) - 1., position.y*2./float(size[1]) - 1., 0., 1.);
+}
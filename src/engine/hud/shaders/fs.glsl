@@ -9,13 +9,38 @@ uniform sampler2D tex;
 uniform sampler2D tex_floating;
 
 uniform bool depth_tex;
+uniform bool floating_tex; // HDR images, drawn without tone mapping
+uniform bool gray_tex; // Single channel textures, drawn in grayscale instead of red
+uniform bool srgb_tex; // sRGB textures, decoded to linear when they're sampled
+uniform vec4 tint;
+uniform float opacity;
+uniform vec4 clip; // Min x, min y, max x, max y on the surface
+
+// The framebuffer isn't sRGB, so the colors have to be written encoded
+vec3 linear_to_srgb(vec3 c) {
+	return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), c));
+}
 
 void main(){
+	// Scissor test, pixels outside of the clip rectangle aren't drawn
+	if (gl_FragCoord.x < clip.x || gl_FragCoord.y < clip.y || gl_FragCoord.x > clip.z || gl_FragCoord.y > clip.w) {
+		discard;
+	}
 	if (depth_tex) {
 		float v = texture(tex_floating,v_uv).r;
 		frag = vec4(vec3(v), 1.0);
+	} else if (floating_tex) {
+		frag = vec4(texture(tex_floating,v_uv).rgb, 1.0);
+	} else if (gray_tex) {
+		float v = texture(tex,v_uv).r;
+		frag = vec4(vec3(v), 1.0);
 	} else {
 		frag = texture(tex,v_uv);
+		if (srgb_tex) {
+			frag.rgb = linear_to_srgb(frag.rgb);
+		}
 	}
+	frag = frag * tint;
+	frag.a *= opacity;
 	//frag_color = vec3(0.5, 0, 0.5);
 }
\ No newline at end of file
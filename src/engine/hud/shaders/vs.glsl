@@ -11,13 +11,18 @@ uniform ivec2 pos;
 uniform float scale;
 uniform uvec2 size;
 uniform uvec2 tex_size;
-uniform float depth;
+uniform float rotation; // Radians, counterclockwise
+uniform vec2 pivot; // In vertex positions
 
 
 void main(){
 	// Create uv pos from pixel pos & tex size
 	v_uv = vec2(float(uv[0])/float(tex_size[0]), float(uv[1])/float(tex_size[1]));
 
+	// Rotate around the pivot before scaling & moving
+	vec2 local = vec2(position) - pivot;
+	local = mat2(cos(rotation), sin(rotation), -sin(rotation), cos(rotation)) * local + pivot;
+
 	// mandatory; tell the GPU to use the position vertex attribute to put the vertex in space
-	gl_Position = vec4((float(position[0])*scale+float(pos[0]))*2./float(size[0]) - 1., (float(position[1])*scale+float(pos[1]))*2./float(size[1]) - 1.,depth,1.);
+	gl_Position = vec4((local.x*scale+float(pos[0]))*2./float(size[0]) - 1., (local.y*scale+float(pos[1]))*2./float(size[1]) - 1.,0.,1.);
 }
\ No newline at end of file
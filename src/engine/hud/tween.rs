@@ -0,0 +1,354 @@
+use std::f32::consts::PI;
+
+use super::layout::Offset;
+use super::Entity;
+
+use super::super::RgbaColor;
+
+/// How a tween goes from 0 to 1 over time
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicIn,
+	CubicOut,
+	CubicInOut,
+	SineInOut,
+	BackOut,    // Goes a bit past the end and comes back
+	ElasticOut, // Wobbles around the end
+	BounceOut,
+}
+
+impl Easing {
+	/// Eased value of `t`, from 0 to 1. It can go out of that range in between for some curves
+	pub fn apply(&self, t: f32) -> f32 {
+		let t = t.max(0.0).min(1.0);
+		match self {
+			Self::Linear => t,
+			Self::QuadIn => t * t,
+			Self::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+			Self::QuadInOut if t < 0.5 => 2.0 * t * t,
+			Self::QuadInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+			Self::CubicIn => t * t * t,
+			Self::CubicOut => 1.0 - (1.0 - t).powi(3),
+			Self::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+			Self::CubicInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+			Self::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+			Self::BackOut => {
+				let (c1, c3) = (1.70158, 2.70158);
+				1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+			}
+			Self::ElasticOut if t == 0.0 || t == 1.0 => t,
+			Self::ElasticOut => {
+				2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+			}
+			Self::BounceOut => {
+				let (n1, d1) = (7.5625, 2.75);
+				if t < 1.0 / d1 {
+					n1 * t * t
+				} else if t < 2.0 / d1 {
+					let t = t - 1.5 / d1;
+					n1 * t * t + 0.75
+				} else if t < 2.5 / d1 {
+					let t = t - 2.25 / d1;
+					n1 * t * t + 0.9375
+				} else {
+					let t = t - 2.625 / d1;
+					n1 * t * t + 0.984375
+				}
+			}
+		}
+	}
+}
+
+/// Property of an entity that a tween changes, with the value it ends at.
+/// It starts from the value the entity has when the tween starts (after its delay)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+	Pos([i32; 2]),    // Replaces the layout, like `Entity::set_pos`
+	Offset([i32; 2]), // Pixel offset of the layout, entities without one aren't moved
+	Scale(f32),
+	Rotation(f32), // Radians
+	Tint(RgbaColor),
+	Opacity(f32),
+}
+
+impl Target {
+	// Values are stored as up to 4 floats, so all of them are interpolated the same way
+	fn value(&self) -> [f32; 4] {
+		match *self {
+			Self::Pos(p) | Self::Offset(p) => [p[0] as f32, p[1] as f32, 0.0, 0.0],
+			Self::Scale(v) | Self::Rotation(v) | Self::Opacity(v) => [v, 0.0, 0.0, 0.0],
+			Self::Tint(c) => {
+				let [r, g, b, a] = c.array();
+				[r as f32, g as f32, b as f32, a as f32]
+			}
+		}
+	}
+
+	fn read(&self, entity: &Entity) -> [f32; 4] {
+		let current = match self {
+			Self::Pos(_) => Self::Pos(entity.pos()),
+			Self::Offset(_) => {
+				let offset = entity.layout().map(|l| l.offset);
+				let pixels = |o: Offset| match o {
+					Offset::Pixels(p) => p,
+					Offset::Percent(_) => 0, // Replaced by pixels
+				};
+				Self::Offset(offset.map_or([0, 0], |o| [pixels(o[0]), pixels(o[1])]))
+			}
+			Self::Scale(_) => Self::Scale(entity.scale()),
+			Self::Rotation(_) => Self::Rotation(entity.rotation()),
+			Self::Tint(_) => Self::Tint(entity.tint()),
+			Self::Opacity(_) => Self::Opacity(entity.opacity()),
+		};
+		current.value()
+	}
+
+	fn write(&self, entity: &mut Entity, v: [f32; 4]) {
+		match self {
+			Self::Pos(_) => entity.set_pos([v[0].round() as i32, v[1].round() as i32]),
+			Self::Offset(_) => {
+				if let Some(layout) = entity.layout().copied() {
+					entity.set_layout(layout.with_offset(
+						Offset::Pixels(v[0].round() as i32),
+						Offset::Pixels(v[1].round() as i32),
+					));
+				}
+			}
+			Self::Scale(_) => entity.set_scale(v[0]),
+			Self::Rotation(_) => entity.set_rotation(v[0]),
+			Self::Tint(_) => {
+				let channel = |c: f32| c.round().max(0.0).min(255.0) as u8;
+				entity.set_tint(RgbaColor::new(
+					channel(v[0]),
+					channel(v[1]),
+					channel(v[2]),
+					channel(v[3]),
+				));
+			}
+			Self::Opacity(_) => entity.set_opacity(v[0]),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+	To {
+		target: Target,
+		duration: f32,
+		easing: Easing,
+		from: Option<[f32; 4]>, // Read when it starts
+	},
+	Sequence(Vec<Tween>, usize), // With the one being played
+	Parallel(Vec<Tween>),
+}
+
+/// Changes properties of an entity over time. Single tweens can be combined in sequences
+/// (one after the other) and parallel groups (all at once), which can be nested
+#[derive(Debug, Clone)]
+pub struct Tween {
+	kind: Kind,
+	easing_set: bool,
+	delay: f32,
+	repeat: Option<u32>, // Times it's played, forever with `None`
+	played: u32,
+	elapsed: f32, // Since the start of this repetition, including the delay
+	finished: bool,
+}
+
+#[allow(dead_code)]
+impl Tween {
+	fn new(kind: Kind) -> Self {
+		Self {
+			kind,
+			easing_set: false,
+			delay: 0.0,
+			repeat: Some(1),
+			played: 0,
+			elapsed: 0.0,
+			finished: false,
+		}
+	}
+
+	/// Changes a property to the `target` value in `duration` seconds
+	pub fn to(target: Target, duration: f32) -> Self {
+		Self::new(Kind::To {
+			target,
+			duration: duration.max(0.0),
+			easing: Easing::Linear,
+			from: None,
+		})
+	}
+
+	/// Does nothing for a while, useful in sequences
+	pub fn wait(duration: f32) -> Self {
+		Self::sequence(Vec::new()).delay(duration)
+	}
+
+	/// Plays the tweens one after the other
+	pub fn sequence(tweens: Vec<Tween>) -> Self {
+		Self::new(Kind::Sequence(tweens, 0))
+	}
+
+	/// Plays the tweens at the same time, it finishes with the longest one
+	pub fn parallel(tweens: Vec<Tween>) -> Self {
+		Self::new(Kind::Parallel(tweens))
+	}
+
+	/// Easing curve of a single tween, or of the tweens in a group that don't have their own
+	pub fn ease(mut self, easing: Easing) -> Self {
+		self.set_easing(easing, true);
+		self
+	}
+
+	fn set_easing(&mut self, new_easing: Easing, explicit: bool) {
+		if self.easing_set && !explicit {
+			return;
+		}
+		self.easing_set = explicit;
+		match &mut self.kind {
+			Kind::To { easing, .. } => *easing = new_easing,
+			Kind::Sequence(tweens, _) | Kind::Parallel(tweens) => {
+				for tween in tweens {
+					tween.set_easing(new_easing, false)
+				}
+			}
+		}
+	}
+
+	/// Waits before starting, every time it's repeated
+	pub fn delay(mut self, seconds: f32) -> Self {
+		self.delay = seconds.max(0.0);
+		self
+	}
+
+	/// Plays it `times` times in total, every one starting from where the last one ended
+	pub fn repeat(mut self, times: u32) -> Self {
+		self.repeat = Some(times.max(1));
+		self
+	}
+
+	/// Plays it again every time it finishes, it never finishes
+	pub fn looped(mut self) -> Self {
+		self.repeat = None;
+		self
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.finished
+	}
+
+	// Back to the start of a repetition, the values are read again when it plays
+	fn reset(&mut self) {
+		self.elapsed = 0.0;
+		self.finished = false;
+		match &mut self.kind {
+			Kind::To { from, .. } => *from = None,
+			Kind::Sequence(tweens, current) => {
+				*current = 0;
+				tweens.iter_mut().for_each(Tween::restart);
+			}
+			Kind::Parallel(tweens) => tweens.iter_mut().for_each(Tween::restart),
+		}
+	}
+
+	/// Plays it again from the start, with all its repetitions
+	pub fn restart(&mut self) {
+		self.played = 0;
+		self.reset()
+	}
+
+	/// Advances the tween `dt` seconds, changing the entity. Returns whether it finished
+	pub fn update(&mut self, entity: &mut Entity, dt: f32) -> bool {
+		self.advance(entity, dt).is_some()
+	}
+
+	// Returns the time left after finishing, `None` if it's still playing
+	fn advance(&mut self, entity: &mut Entity, mut dt: f32) -> Option<f32> {
+		if self.finished {
+			return Some(dt);
+		}
+		loop {
+			let start_dt = dt;
+			if self.elapsed < self.delay {
+				let step = (self.delay - self.elapsed).min(dt);
+				self.elapsed += step;
+				dt -= step;
+				if self.elapsed < self.delay {
+					return None;
+				}
+			}
+			let left = match &mut self.kind {
+				Kind::To {
+					target,
+					duration,
+					easing,
+					from,
+				} => {
+					let from = *from.get_or_insert_with(|| target.read(entity));
+					let time = self.elapsed - self.delay + dt;
+					let t = if *duration > 0.0 {
+						(time / *duration).min(1.0)
+					} else {
+						1.0
+					};
+					let (to, eased) = (target.value(), easing.apply(t));
+					let mut v = [0.0; 4];
+					for i in 0..4 {
+						v[i] = from[i] + (to[i] - from[i]) * eased;
+					}
+					target.write(entity, v);
+					self.elapsed += dt;
+					if time >= *duration {
+						Some(time - *duration)
+					} else {
+						None
+					}
+				}
+				Kind::Sequence(tweens, current) => {
+					while *current < tweens.len() {
+						match tweens[*current].advance(entity, dt) {
+							Some(left) => {
+								dt = left;
+								*current += 1;
+							}
+							None => return None,
+						}
+					}
+					Some(dt)
+				}
+				Kind::Parallel(tweens) => {
+					let mut left = Some(dt);
+					for tween in tweens {
+						left = match (left, tween.advance(entity, dt)) {
+							(Some(a), Some(b)) => Some(a.min(b)),
+							_ => None,
+						};
+					}
+					left
+				}
+			}?;
+
+			self.played += 1;
+			match self.repeat {
+				Some(times) if self.played >= times => {
+					self.finished = true;
+					return Some(left);
+				}
+				_ => {
+					// Without any time passing, it would repeat forever in this update
+					if left >= start_dt {
+						return None;
+					}
+					self.reset();
+					dt = left;
+				}
+			}
+		}
+	}
+}
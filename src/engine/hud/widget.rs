@@ -0,0 +1,1068 @@
+use luminance::context::GraphicsContext;
+use luminance::pipeline::{Pipeline, ShadingGate};
+
+use luminance_glfw::{Action, MouseButton, WindowEvent};
+
+use std::path::Path;
+
+use super::layer::Layer;
+use super::layout::{intersect_clip, Anchor, Layout};
+use super::{Entity, Offset, Renderer, Vertex, VertexPosition, VertexUV};
+
+use super::super::assets::{AssetServer, Handle};
+use super::super::error::{Error, Result};
+use super::super::text::{Font, FontStyle, FontWeight};
+use super::super::texture::{AnyTexture, TextureSettings};
+use super::super::utils::load_with_settings;
+use super::super::{FileLoader, RgbaColor};
+
+// Every color of the theme is a block of the palette texture, only its inner pixels are used
+const SWATCH_SIZE: u32 = 4;
+
+// Pixels scrolled by a step of the mouse wheel
+const SCROLL_STEP: f32 = 40.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Swatch {
+	Panel,
+	Normal,
+	Hover,
+	Pressed,
+	Accent,
+	Track,
+}
+
+const SWATCHES: [Swatch; 6] = [
+	Swatch::Panel,
+	Swatch::Normal,
+	Swatch::Hover,
+	Swatch::Pressed,
+	Swatch::Accent,
+	Swatch::Track,
+];
+
+/// Colors and sizes of the widgets, their textures are generated from it
+#[derive(Debug, Clone)]
+pub struct Theme {
+	pub font: Font,
+	pub panel: RgbaColor,
+	pub normal: RgbaColor, // Buttons, check boxes and slider knobs
+	pub hover: RgbaColor,
+	pub pressed: RgbaColor,
+	pub accent: RgbaColor, // Check marks and the filled part of sliders and progress bars
+	pub track: RgbaColor,  // The empty part of sliders and progress bars
+	pub padding: u32,      // Between a check box and its label, and around the children of a panel
+	pub check_size: u32,
+	pub scrollbar_width: u32,
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		Self {
+			font: Font::new("Roboto", FontWeight::Regular, FontStyle::Regular, 20.0),
+			panel: RgbaColor::new(20, 20, 30, 200),
+			normal: RgbaColor::new(60, 60, 80, 255),
+			hover: RgbaColor::new(80, 80, 110, 255),
+			pressed: RgbaColor::new(40, 40, 55, 255),
+			accent: RgbaColor::new(90, 170, 250, 255),
+			track: RgbaColor::new(35, 35, 45, 255),
+			padding: 8,
+			check_size: 20,
+			scrollbar_width: 6,
+		}
+	}
+}
+
+impl Theme {
+	fn color(&self, swatch: Swatch) -> RgbaColor {
+		match swatch {
+			Swatch::Panel => self.panel,
+			Swatch::Normal => self.normal,
+			Swatch::Hover => self.hover,
+			Swatch::Pressed => self.pressed,
+			Swatch::Accent => self.accent,
+			Swatch::Track => self.track,
+		}
+	}
+}
+
+/// Sent by `Ui::event` when the user interacts with a widget, with the name of the widget
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+	Clicked(String),
+	Toggled(String, bool), // Check boxes
+	Changed(String, f32),  // Sliders, from 0 to 1
+	Scrolled(String, f32), // Scroll views, from 0 (top) to 1 (bottom)
+}
+
+pub enum WidgetKind {
+	Label,
+	Button,
+	Checkbox(bool),
+	Slider(f32),      // From 0 to 1
+	ProgressBar(f32), // From 0 to 1
+	Panel(Vec<Widget>),
+	ScrollView {
+		children: Vec<Widget>,
+		scroll: u32,  // Pixels scrolled down from the top
+		content: u32, // Height of the children, with the padding
+	},
+}
+
+/// A part of the UI, made of HUD entities. Created by the `Ui`
+pub struct Widget {
+	name: String,
+	kind: WidgetKind,
+	layout: Layout, // In the parent panel, or in the surface
+	size: [u32; 2],
+	entities: Vec<Entity>,  // In drawing order, their meaning depends on the kind
+	rect: [i32; 4],         // On the surface, from the last `Ui::layout`
+	clip: Option<[i32; 4]>, // From the scroll views it's in, only that part can be seen and clicked
+	hovered: bool,
+	pressed: bool,
+}
+
+#[allow(dead_code)]
+impl Widget {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn kind(&self) -> &WidgetKind {
+		&self.kind
+	}
+
+	pub fn size(&self) -> [u32; 2] {
+		self.size
+	}
+
+	pub fn set_layout(&mut self, layout: Layout) {
+		self.layout = layout
+	}
+
+	/// Min x, min y, max x, max y on the surface
+	pub fn rect(&self) -> [i32; 4] {
+		self.rect
+	}
+
+	pub fn is_hovered(&self) -> bool {
+		self.hovered
+	}
+
+	/// Whether a check box is checked, `None` for the other widgets
+	pub fn is_checked(&self) -> Option<bool> {
+		match self.kind {
+			WidgetKind::Checkbox(checked) => Some(checked),
+			_ => None,
+		}
+	}
+
+	pub fn set_checked(&mut self, checked: bool) -> Result<()> {
+		match &mut self.kind {
+			WidgetKind::Checkbox(c) => {
+				*c = checked;
+				Ok(())
+			}
+			_ => Err(Error::wrong_kind(&self.name, "check box")),
+		}
+	}
+
+	/// Value of a slider or progress bar, `None` for the other widgets
+	pub fn value(&self) -> Option<f32> {
+		match self.kind {
+			WidgetKind::Slider(value) | WidgetKind::ProgressBar(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	/// Sets the value of a slider or a progress bar, clamped between 0 and 1
+	pub fn set_value(&mut self, value: f32) -> Result<()> {
+		let value = value.max(0.0).min(1.0);
+		match &mut self.kind {
+			WidgetKind::Slider(v) | WidgetKind::ProgressBar(v) => *v = value,
+			_ => return Err(Error::wrong_kind(&self.name, "slider or progress bar")),
+		}
+		self.arrange();
+		Ok(())
+	}
+
+	/// Changes the text of a label, button or check box. Buttons keep their size
+	pub fn set_text<T: ToString, C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		theme: &Theme,
+		text: &T,
+	) -> Result<()> {
+		let i = match self.kind {
+			WidgetKind::Label => 0,
+			WidgetKind::Button => 1,
+			WidgetKind::Checkbox(_) => 2,
+			_ => return Ok(()),
+		};
+		self.entities[i].update_text(assets, file_loader, surface, text, &theme.font)?;
+		self.fit_text(theme);
+		Ok(())
+	}
+
+	// Labels and check boxes are as big as their text
+	fn fit_text(&mut self, theme: &Theme) {
+		match self.kind {
+			WidgetKind::Label => self.size = entity_size(&self.entities[0]),
+			WidgetKind::Checkbox(_) => self.size = checkbox_size(theme, &self.entities[2]),
+			_ => (),
+		}
+		self.arrange();
+	}
+
+	// Rebuilds the entities that use the file at `path`, like the text when its font changes
+	fn reload<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		theme: &Theme,
+		path: &Path,
+	) -> Result<()> {
+		let mut reloaded = false;
+		for entity in &mut self.entities {
+			if entity.uses_file(path) {
+				entity.reload(assets, file_loader, surface)?;
+				reloaded = true;
+			}
+		}
+		if reloaded {
+			self.fit_text(theme);
+		}
+		Ok(())
+	}
+
+	/// Children of a panel or scroll view, empty for the other widgets
+	pub fn children(&self) -> &[Widget] {
+		match &self.kind {
+			WidgetKind::Panel(children) | WidgetKind::ScrollView { children, .. } => children,
+			_ => &[],
+		}
+	}
+
+	fn children_mut(&mut self) -> &mut [Widget] {
+		match &mut self.kind {
+			WidgetKind::Panel(children) | WidgetKind::ScrollView { children, .. } => children,
+			_ => &mut [],
+		}
+	}
+
+	/// Adds a widget at the bottom of a scroll view, like a new line of a log
+	pub fn add_child(&mut self, theme: &Theme, mut child: Widget) -> Result<()> {
+		match &mut self.kind {
+			WidgetKind::ScrollView {
+				children, content, ..
+			} => {
+				child.layout = stacked(theme, *content);
+				*content += child.size[1] + theme.padding;
+				children.push(child);
+			}
+			_ => return Err(Error::wrong_kind(&self.name, "scroll view")),
+		}
+		self.arrange();
+		Ok(())
+	}
+
+	/// Removes the child `index` of a scroll view (like the oldest line of a log), the ones below move up.
+	/// `None` if there's no such child
+	pub fn remove_child(&mut self, theme: &Theme, index: usize) -> Result<Option<Widget>> {
+		let child = match &mut self.kind {
+			WidgetKind::ScrollView {
+				children,
+				scroll,
+				content,
+			} => {
+				if index >= children.len() {
+					return Ok(None);
+				}
+				let top = stack(theme, &mut children[..index])[1];
+				let child = children.remove(index);
+				*content = stack(theme, children)[1];
+				// What was shown stays in place when a child above it goes away
+				if top < *scroll {
+					*scroll = scroll.saturating_sub(child.size[1] + theme.padding);
+				}
+				*scroll = (*scroll).min(content.saturating_sub(self.size[1]));
+				child
+			}
+			_ => return Err(Error::wrong_kind(&self.name, "scroll view")),
+		};
+		self.arrange();
+		Ok(Some(child))
+	}
+
+	/// Pixels a scroll view is scrolled down from the top, `None` for the other widgets
+	pub fn scroll(&self) -> Option<u32> {
+		match self.kind {
+			WidgetKind::ScrollView { scroll, .. } => Some(scroll),
+			_ => None,
+		}
+	}
+
+	/// Scrolls a scroll view down from the top, up to its bottom (`u32::MAX` goes to the bottom)
+	pub fn set_scroll(&mut self, pixels: u32) -> Result<()> {
+		let max = self.max_scroll();
+		match &mut self.kind {
+			WidgetKind::ScrollView { scroll, .. } => *scroll = pixels.min(max),
+			_ => return Err(Error::wrong_kind(&self.name, "scroll view")),
+		}
+		self.arrange();
+		Ok(())
+	}
+
+	// How far a scroll view can be scrolled, 0 if its children fit
+	fn max_scroll(&self) -> u32 {
+		match self.kind {
+			WidgetKind::ScrollView { content, .. } => content.saturating_sub(self.size[1]),
+			_ => 0,
+		}
+	}
+
+	fn is_interactive(&self) -> bool {
+		match self.kind {
+			WidgetKind::Button | WidgetKind::Checkbox(_) | WidgetKind::Slider(_) => true,
+			_ => false,
+		}
+	}
+
+	fn contains(&self, p: [i32; 2]) -> bool {
+		let rect = intersect_clip(Some(self.rect), self.clip).unwrap();
+		p[0] >= rect[0] && p[0] < rect[2] && p[1] >= rect[1] && p[1] < rect[3]
+	}
+
+	// Slider value under the cursor
+	fn value_at(&self, cursor: [i32; 2]) -> f32 {
+		let knob = self.size[1] as i32;
+		let width = (self.rect[2] - self.rect[0] - knob).max(1);
+		(cursor[0] - self.rect[0] - knob / 2) as f32 / width as f32
+	}
+
+	// The entity that shows whether the widget is hovered or pressed
+	fn state_entity(&self) -> Option<usize> {
+		match self.kind {
+			WidgetKind::Button | WidgetKind::Checkbox(_) => Some(0),
+			WidgetKind::Slider(_) => Some(2),
+			_ => None,
+		}
+	}
+
+	fn set_hovered(&mut self, hovered: bool) {
+		self.hovered = hovered;
+		self.update_state()
+	}
+
+	fn set_pressed(&mut self, pressed: bool) {
+		self.pressed = pressed;
+		self.update_state()
+	}
+
+	fn update_state(&mut self) {
+		let swatch = if self.pressed {
+			Swatch::Pressed
+		} else if self.hovered {
+			Swatch::Hover
+		} else {
+			Swatch::Normal
+		};
+		if let Some(i) = self.state_entity() {
+			self.entities[i].update_uv(&swatch_uv(swatch));
+		}
+	}
+
+	// Sizes the entities for the current value, relative to the bottom left of the widget
+	fn arrange(&mut self) {
+		let [w, h] = self.size;
+		match self.kind {
+			WidgetKind::Slider(value) => {
+				// The fill goes up to the middle of the knob
+				let track = (h / 3).max(1);
+				let fill = (value * w.saturating_sub(h) as f32) as u32 + h / 2;
+				self.entities[0].update_pos(&quad_pos([w, track]));
+				self.entities[1].update_pos(&quad_pos([fill, track]));
+			}
+			WidgetKind::ProgressBar(value) => {
+				self.entities[1].update_pos(&quad_pos([(value * w as f32) as u32, h]));
+			}
+			WidgetKind::ScrollView { content, .. } => {
+				// The thumb is as much of the track as the part of the children that can be seen
+				let width = self.entities[1].extent()[2] as u32;
+				let thumb = (h as f32 * h as f32 / content.max(1) as f32) as u32;
+				self.entities[1].update_pos(&quad_pos([width, thumb.max(width * 2).min(h)]));
+			}
+			_ => (),
+		}
+	}
+
+	// Positions of the entities relative to the bottom left of the widget
+	fn offsets(&self) -> Vec<[i32; 2]> {
+		let [w, h] = [self.size[0] as i32, self.size[1] as i32];
+		let centered = |e: &Entity, x: i32| [x, (h - entity_size(e)[1] as i32) / 2];
+		match &self.kind {
+			WidgetKind::Label | WidgetKind::ProgressBar(_) | WidgetKind::Panel(_) => {
+				vec![[0, 0]; self.entities.len()]
+			}
+			WidgetKind::Button => {
+				let [tw, _] = entity_size(&self.entities[1]);
+				vec![[0, 0], centered(&self.entities[1], (w - tw as i32) / 2)]
+			}
+			WidgetKind::Checkbox(_) => {
+				let [check, _] = entity_size(&self.entities[0]);
+				let [mark, _] = entity_size(&self.entities[1]);
+				let (check, mark) = (check as i32, mark as i32);
+				// The label is on the right edge, after the padding
+				let text_x = w - entity_size(&self.entities[2])[0] as i32;
+				vec![
+					[0, (h - check) / 2],
+					[(check - mark) / 2, (h - mark) / 2],
+					centered(&self.entities[2], text_x),
+				]
+			}
+			WidgetKind::Slider(value) => {
+				let track = (h / 3).max(1);
+				let knob_x = (value * (w - h) as f32) as i32;
+				vec![[0, (h - track) / 2], [0, (h - track) / 2], [knob_x, 0]]
+			}
+			WidgetKind::ScrollView { scroll, .. } => {
+				// The thumb goes down the right edge as the view scrolls
+				let [bar, thumb] = entity_size(&self.entities[1]);
+				let max = self.max_scroll().max(1) as f32;
+				let y =
+					h - thumb as i32 - (*scroll as f32 / max * (h - thumb as i32) as f32) as i32;
+				vec![[0, 0], [w - bar as i32, y]]
+			}
+		}
+	}
+
+	// Places the widget in its parent, then its entities and children. Only the `clip` part of it can be seen
+	fn place(&mut self, parent: [i32; 4], layer: Layer, clip: Option<[i32; 4]>) {
+		let parent_size = [
+			(parent[2] - parent[0]).max(0) as u32,
+			(parent[3] - parent[1]).max(0) as u32,
+		];
+		let pos = self.layout.position(
+			parent_size,
+			[0.0, 0.0],
+			[self.size[0] as f32, self.size[1] as f32],
+		);
+		let pos = [parent[0] + pos[0], parent[1] + pos[1]];
+		self.rect = [
+			pos[0],
+			pos[1],
+			pos[0] + self.size[0] as i32,
+			pos[1] + self.size[1] as i32,
+		];
+		self.clip = clip;
+		for (entity, offset) in self.entities.iter_mut().zip(self.offsets()) {
+			entity.set_pos([pos[0] + offset[0], pos[1] + offset[1]]);
+			entity.set_layer(layer);
+			entity.set_clip(clip);
+		}
+		let rect = self.rect;
+		match &mut self.kind {
+			WidgetKind::Panel(children) => {
+				for child in children {
+					child.place(rect, layer, clip);
+				}
+			}
+			WidgetKind::ScrollView {
+				children, scroll, ..
+			} => {
+				// The children are moved up by the scroll, and cut at the edges of the view
+				let scroll = *scroll as i32;
+				let content = [rect[0], rect[1] + scroll, rect[2], rect[3] + scroll];
+				let clip = intersect_clip(clip, Some(rect));
+				for child in children {
+					child.place(content, layer, clip);
+				}
+			}
+			_ => (),
+		}
+	}
+
+	fn visible_entities<'w>(&'w self, entities: &mut Vec<&'w Entity>) {
+		// Scrolled out of view, its children may still be in it
+		let [min_x, min_y, max_x, max_y] = intersect_clip(Some(self.rect), self.clip).unwrap();
+		let hidden = min_x == max_x || min_y == max_y;
+		for (i, entity) in self.entities.iter().enumerate() {
+			match (&self.kind, i) {
+				_ if hidden => continue,
+				// The check mark is only drawn when the box is checked
+				(WidgetKind::Checkbox(false), 1) => continue,
+				// Neither is the scroll bar when there's nothing to scroll
+				(WidgetKind::ScrollView { .. }, 1) if self.max_scroll() == 0 => continue,
+				_ => (),
+			}
+			entities.push(entity);
+		}
+		for child in self.children() {
+			child.visible_entities(entities);
+		}
+	}
+}
+
+/// Widgets on top of the HUD, with their shared palette texture and the cursor state
+pub struct Ui {
+	theme: Theme,
+	palette: Handle<AnyTexture>,
+	widgets: Vec<Widget>,
+	surface_size: [u32; 2],
+	cursor: [i32; 2], // From the bottom left, like positions
+	pressed: Option<String>,
+	layer: Layer,
+}
+
+#[allow(dead_code)]
+impl Ui {
+	pub fn new<C: GraphicsContext>(surface: &mut C, theme: Theme) -> Result<Self> {
+		let mut img = image::RgbaImage::new(SWATCH_SIZE * SWATCHES.len() as u32, SWATCH_SIZE);
+		for (x, _, pixel) in img.enumerate_pixels_mut() {
+			*pixel = image::Rgba(theme.color(SWATCHES[(x / SWATCH_SIZE) as usize]).array());
+		}
+		// Nearest filtering, so the swatches don't blend together
+		let tex = load_with_settings(surface, img, &TextureSettings::pixel_art())?;
+		Ok(Self {
+			theme,
+			palette: Handle::new(tex.into()),
+			widgets: Vec::new(),
+			surface_size: [0, 0],
+			cursor: [0, 0],
+			pressed: None,
+			layer: Layer::Ui,
+		})
+	}
+
+	pub fn theme(&self) -> &Theme {
+		&self.theme
+	}
+
+	/// Layer the widgets are drawn in, they're drawn in the order they were added
+	pub fn set_layer(&mut self, layer: Layer) {
+		self.layer = layer
+	}
+
+	pub fn add(&mut self, widget: Widget) {
+		self.widgets.push(widget)
+	}
+
+	/// Adds a widget at the bottom of a scroll view
+	pub fn add_child(&mut self, parent: &str, child: Widget) -> Result<()> {
+		let theme = &self.theme;
+		find_mut(&mut self.widgets, parent)
+			.ok_or_else(|| Error::unknown("widget", parent))?
+			.add_child(theme, child)
+	}
+
+	/// Removes a child of a scroll view, the ones below move up
+	pub fn remove_child(&mut self, parent: &str, index: usize) -> Result<Option<Widget>> {
+		let theme = &self.theme;
+		find_mut(&mut self.widgets, parent)
+			.ok_or_else(|| Error::unknown("widget", parent))?
+			.remove_child(theme, index)
+	}
+
+	/// Removes a top level widget
+	pub fn remove(&mut self, name: &str) -> Option<Widget> {
+		let i = self.widgets.iter().position(|w| w.name == name)?;
+		Some(self.widgets.remove(i))
+	}
+
+	/// Finds a widget by name, also inside panels
+	pub fn get(&self, name: &str) -> Option<&Widget> {
+		find(&self.widgets, name)
+	}
+
+	pub fn get_mut(&mut self, name: &str) -> Option<&mut Widget> {
+		find_mut(&mut self.widgets, name)
+	}
+
+	// A quad of one color of the palette
+	fn quad<C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		swatch: Swatch,
+		size: [u32; 2],
+	) -> Result<Entity> {
+		let uv = swatch_uv(swatch);
+		let vertices = quad_pos(size)
+			.iter()
+			.zip(uv.iter())
+			.map(|(pos, uv)| Vertex::new(*pos, *uv))
+			.collect::<Vec<_>>();
+		Entity::new_from_handle(
+			surface,
+			&vertices,
+			&[0, 1, 2, 0, 2, 3],
+			self.palette.clone(),
+		)
+	}
+
+	fn text<T: ToString, C: GraphicsContext>(
+		&self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		text: &T,
+	) -> Result<Entity> {
+		Entity::new_entity_from_string(
+			assets,
+			file_loader,
+			surface,
+			text.to_string(),
+			&self.theme.font,
+		)
+	}
+
+	fn widget<T: ToString>(
+		name: &T,
+		kind: WidgetKind,
+		layout: Layout,
+		size: [u32; 2],
+		entities: Vec<Entity>,
+	) -> Widget {
+		let mut widget = Widget {
+			name: name.to_string(),
+			kind,
+			layout,
+			size,
+			entities,
+			rect: [0; 4],
+			clip: None,
+			hovered: false,
+			pressed: false,
+		};
+		widget.arrange();
+		widget
+	}
+
+	pub fn label<N: ToString, T: ToString, C: GraphicsContext>(
+		&self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		name: &N,
+		text: &T,
+		layout: Layout,
+	) -> Result<Widget> {
+		let text = self.text(assets, file_loader, surface, text)?;
+		let size = entity_size(&text);
+		Ok(Self::widget(
+			name,
+			WidgetKind::Label,
+			layout,
+			size,
+			vec![text],
+		))
+	}
+
+	pub fn button<N: ToString, T: ToString, C: GraphicsContext>(
+		&self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		name: &N,
+		text: &T,
+		size: [u32; 2],
+		layout: Layout,
+	) -> Result<Widget> {
+		let entities = vec![
+			self.quad(surface, Swatch::Normal, size)?,
+			self.text(assets, file_loader, surface, text)?,
+		];
+		Ok(Self::widget(
+			name,
+			WidgetKind::Button,
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	/// A check box with its label on the right, the whole widget can be clicked
+	pub fn checkbox<N: ToString, T: ToString, C: GraphicsContext>(
+		&self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		name: &N,
+		text: &T,
+		checked: bool,
+		layout: Layout,
+	) -> Result<Widget> {
+		let check = self.theme.check_size;
+		let text = self.text(assets, file_loader, surface, text)?;
+		let size = checkbox_size(&self.theme, &text);
+		let entities = vec![
+			self.quad(surface, Swatch::Normal, [check, check])?,
+			self.quad(surface, Swatch::Accent, [check / 2, check / 2])?,
+			text,
+		];
+		Ok(Self::widget(
+			name,
+			WidgetKind::Checkbox(checked),
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	/// A horizontal slider, its value goes from 0 (left) to 1 (right)
+	pub fn slider<T: ToString, C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		name: &T,
+		value: f32,
+		size: [u32; 2],
+		layout: Layout,
+	) -> Result<Widget> {
+		let entities = vec![
+			self.quad(surface, Swatch::Track, size)?,
+			self.quad(surface, Swatch::Accent, size)?,
+			self.quad(surface, Swatch::Normal, [size[1], size[1]])?,
+		];
+		let value = value.max(0.0).min(1.0);
+		Ok(Self::widget(
+			name,
+			WidgetKind::Slider(value),
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	pub fn progress_bar<T: ToString, C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		name: &T,
+		value: f32,
+		size: [u32; 2],
+		layout: Layout,
+	) -> Result<Widget> {
+		let entities = vec![
+			self.quad(surface, Swatch::Track, size)?,
+			self.quad(surface, Swatch::Accent, size)?,
+		];
+		let value = value.max(0.0).min(1.0);
+		Ok(Self::widget(
+			name,
+			WidgetKind::ProgressBar(value),
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	/// A background for other widgets, which are laid out inside it instead of the surface
+	pub fn panel<T: ToString, C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		name: &T,
+		size: [u32; 2],
+		layout: Layout,
+		children: Vec<Widget>,
+	) -> Result<Widget> {
+		let entities = vec![self.quad(surface, Swatch::Panel, size)?];
+		Ok(Self::widget(
+			name,
+			WidgetKind::Panel(children),
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	/// A panel with its children in a column from the top, separated by the padding of the theme.
+	/// Their layouts are replaced, and the panel is as big as they need
+	pub fn column<T: ToString, C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		name: &T,
+		layout: Layout,
+		mut children: Vec<Widget>,
+	) -> Result<Widget> {
+		let padding = self.theme.padding;
+		let [width, height] = stack(&self.theme, &mut children);
+		self.panel(
+			surface,
+			name,
+			[width + padding * 2, height],
+			layout,
+			children,
+		)
+	}
+
+	/// A panel of `size` pixels with its children in a column, like `column`, which scrolls with the
+	/// mouse wheel when they don't fit. The parts of the children outside of it aren't drawn
+	pub fn scroll_view<T: ToString, C: GraphicsContext>(
+		&self,
+		surface: &mut C,
+		name: &T,
+		size: [u32; 2],
+		layout: Layout,
+		mut children: Vec<Widget>,
+	) -> Result<Widget> {
+		let [_, content] = stack(&self.theme, &mut children);
+		let bar = self.theme.scrollbar_width;
+		let entities = vec![
+			self.quad(surface, Swatch::Panel, size)?,
+			self.quad(surface, Swatch::Normal, [bar, bar])?,
+		];
+		Ok(Self::widget(
+			name,
+			WidgetKind::ScrollView {
+				children,
+				scroll: 0,
+				content,
+			},
+			layout,
+			size,
+			entities,
+		))
+	}
+
+	/// Rebuilds the widgets that use the file at `path` (relative to the assets dir) after it changed on disk.
+	/// Invalidate it in the `AssetServer` first
+	pub fn reload<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+		path: &Path,
+	) -> Result<()> {
+		let theme = &self.theme;
+		let mut result = Ok(());
+		for_each_mut(&mut self.widgets, &mut |w| {
+			if result.is_ok() {
+				result = w.reload(assets, file_loader, surface, theme, path);
+			}
+		});
+		result
+	}
+
+	/// Places every widget for the surface size, call it before `render` every frame (it's cheap)
+	pub fn layout(&mut self, surface_size: [u32; 2]) {
+		self.surface_size = surface_size;
+		let surface = [0, 0, surface_size[0] as i32, surface_size[1] as i32];
+		for widget in &mut self.widgets {
+			widget.place(surface, self.layer, None);
+		}
+	}
+
+	// Hovers the widgets under the cursor
+	fn update_hovered(&mut self) {
+		let cursor = self.cursor;
+		for_each_mut(&mut self.widgets, &mut |w| {
+			let hovered = w.contains(cursor);
+			if hovered != w.hovered {
+				w.set_hovered(hovered)
+			}
+		});
+	}
+
+	/// Updates the hovered and pressed widgets, returns what the user did
+	pub fn event(&mut self, event: &WindowEvent) -> Vec<UiEvent> {
+		let mut events = Vec::new();
+		match event {
+			WindowEvent::FramebufferSize(x, y) => self.layout([*x as u32, *y as u32]),
+			WindowEvent::CursorPos(x, y) => {
+				// GLFW counts from the top
+				self.cursor = [*x as i32, self.surface_size[1] as i32 - *y as i32];
+				let cursor = self.cursor;
+				self.update_hovered();
+				if let Some(name) = &self.pressed {
+					if let Some(w) = find_mut(&mut self.widgets, name) {
+						if let WidgetKind::Slider(_) = w.kind {
+							let _ = w.set_value(w.value_at(cursor));
+							events.push(UiEvent::Changed(w.name.clone(), w.value().unwrap()));
+						}
+					}
+				}
+			}
+			WindowEvent::MouseButton(MouseButton::Button1, Action::Press, _) => {
+				// The last widget drawn under the cursor is the one on top
+				let cursor = self.cursor;
+				let mut top = None;
+				for_each_mut(&mut self.widgets, &mut |w| {
+					if w.is_interactive() && w.contains(cursor) {
+						top = Some(w.name.clone())
+					}
+				});
+				if let Some(w) = top.and_then(|name| find_mut(&mut self.widgets, &name)) {
+					w.set_pressed(true);
+					if let WidgetKind::Slider(_) = w.kind {
+						let _ = w.set_value(w.value_at(cursor));
+						events.push(UiEvent::Changed(w.name.clone(), w.value().unwrap()));
+					}
+					self.pressed = Some(w.name.clone());
+				}
+			}
+			WindowEvent::Scroll(_, y) => {
+				// The innermost scroll view under the cursor that can scroll
+				let cursor = self.cursor;
+				let mut top = None;
+				for_each_mut(&mut self.widgets, &mut |w| {
+					if w.max_scroll() > 0 && w.contains(cursor) {
+						top = Some(w.name.clone())
+					}
+				});
+				if let Some(w) = top.and_then(|name| find_mut(&mut self.widgets, &name)) {
+					let old = w.scroll().unwrap();
+					let scroll = (old as f32 - *y as f32 * SCROLL_STEP).max(0.0) as u32;
+					let _ = w.set_scroll(scroll);
+					let scroll = w.scroll().unwrap();
+					if scroll != old {
+						let fraction = scroll as f32 / w.max_scroll() as f32;
+						events.push(UiEvent::Scrolled(w.name.clone(), fraction));
+						// What's under the cursor moved
+						self.layout(self.surface_size);
+						self.update_hovered();
+					}
+				}
+			}
+			WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _) => {
+				let pressed = self.pressed.take();
+				if let Some(w) = pressed.and_then(|name| find_mut(&mut self.widgets, &name)) {
+					w.set_pressed(false);
+					// Releasing outside of the widget cancels the click
+					if w.hovered {
+						match w.kind {
+							WidgetKind::Button => events.push(UiEvent::Clicked(w.name.clone())),
+							WidgetKind::Checkbox(checked) => {
+								w.kind = WidgetKind::Checkbox(!checked);
+								events.push(UiEvent::Toggled(w.name.clone(), !checked));
+							}
+							_ => (),
+						}
+					}
+				}
+			}
+			_ => (),
+		}
+		events
+	}
+
+	/// Whether the cursor is over a widget, so the click shouldn't go to the game
+	pub fn is_hovered(&self) -> bool {
+		let mut hovered = false;
+		for_each(&self.widgets, &mut |w| hovered |= w.hovered);
+		hovered
+	}
+
+	pub fn render<C: GraphicsContext>(
+		&self,
+		renderer: &Renderer,
+		shd_gate: &mut ShadingGate<'_, C>,
+		pipeline: &Pipeline,
+		size: &[u32; 2],
+	) {
+		renderer.render_entities(self.entities(), shd_gate, pipeline, size, None);
+	}
+
+	/// Entities of the visible parts of the widgets, to draw them with a `Batcher`
+	pub fn entities(&self) -> Vec<&Entity> {
+		let mut entities = Vec::new();
+		for widget in &self.widgets {
+			widget.visible_entities(&mut entities);
+		}
+		entities
+	}
+}
+
+fn for_each(widgets: &[Widget], f: &mut dyn FnMut(&Widget)) {
+	for widget in widgets {
+		f(widget);
+		for_each(widget.children(), f);
+	}
+}
+
+fn for_each_mut(widgets: &mut [Widget], f: &mut dyn FnMut(&mut Widget)) {
+	for widget in widgets {
+		f(widget);
+		for_each_mut(widget.children_mut(), f);
+	}
+}
+
+fn find<'w>(widgets: &'w [Widget], name: &str) -> Option<&'w Widget> {
+	for widget in widgets {
+		if widget.name == name {
+			return Some(widget);
+		}
+		if let Some(found) = find(widget.children(), name) {
+			return Some(found);
+		}
+	}
+	None
+}
+
+fn find_mut<'w>(widgets: &'w mut [Widget], name: &str) -> Option<&'w mut Widget> {
+	for widget in widgets {
+		if widget.name == name {
+			return Some(widget);
+		}
+		if let Some(found) = find_mut(widget.children_mut(), name) {
+			return Some(found);
+		}
+	}
+	None
+}
+
+// Layout of a child in a column, `y` pixels below the top of the panel
+fn stacked(theme: &Theme, y: u32) -> Layout {
+	Layout::new(Anchor::TopLeft).with_offset(
+		Offset::Pixels(theme.padding as i32),
+		Offset::Pixels(-(y as i32)),
+	)
+}
+
+// Puts the widgets in a column from the top, returns the widest one and the height of the column
+fn stack(theme: &Theme, children: &mut [Widget]) -> [u32; 2] {
+	let mut y = theme.padding;
+	let mut width = 0;
+	for child in children {
+		child.layout = stacked(theme, y);
+		y += child.size[1] + theme.padding;
+		width = width.max(child.size[0]);
+	}
+	[width, y]
+}
+
+fn checkbox_size(theme: &Theme, text: &Entity) -> [u32; 2] {
+	let [tw, th] = entity_size(text);
+	[
+		theme.check_size + theme.padding + tw,
+		theme.check_size.max(th),
+	]
+}
+
+fn entity_size(entity: &Entity) -> [u32; 2] {
+	let [min_x, min_y, max_x, max_y] = entity.extent();
+	[(max_x - min_x) as u32, (max_y - min_y) as u32]
+}
+
+// Same order as every other quad: bottom left, bottom right, top right, top left
+pub(super) fn quad_pos(size: [u32; 2]) -> [VertexPosition; 4] {
+	let [w, h] = [size[0] as i32, size[1] as i32];
+	[
+		VertexPosition::new([0, 0]),
+		VertexPosition::new([w, 0]),
+		VertexPosition::new([w, h]),
+		VertexPosition::new([0, h]),
+	]
+}
+
+fn swatch_uv(swatch: Swatch) -> [VertexUV; 4] {
+	let i = SWATCHES.iter().position(|s| *s == swatch).unwrap() as u32;
+	let (x0, x1) = (i * SWATCH_SIZE + 1, (i + 1) * SWATCH_SIZE - 1);
+	let (y0, y1) = (1, SWATCH_SIZE - 1);
+	[
+		VertexUV::new([x0, y0]),
+		VertexUV::new([x1, y0]),
+		VertexUV::new([x1, y1]),
+		VertexUV::new([x0, y1]),
+	]
+}
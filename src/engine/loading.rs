@@ -0,0 +1,140 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pool of worker threads to decode and parse assets without blocking the main thread.
+/// GPU uploads still have to happen on the main thread, once the request is done
+pub struct Loader {
+	jobs: Option<Sender<Job>>,
+	workers: Vec<JoinHandle<()>>,
+	requested: Arc<AtomicUsize>,
+	finished: Arc<AtomicUsize>,
+}
+
+#[allow(dead_code)]
+impl Loader {
+	pub fn new(threads: usize) -> Self {
+		let (jobs, rx) = channel::<Job>();
+		let rx = Arc::new(Mutex::new(rx));
+		let finished = Arc::new(AtomicUsize::new(0));
+		let mut workers = Vec::new();
+		for i in 0..threads.max(1) {
+			let rx = rx.clone();
+			let finished = finished.clone();
+			workers.push(
+				thread::Builder::new()
+					.name(format!("loader-{}", i))
+					.spawn(move || loop {
+						// The lock is released as soon as there's a job, so the others can wait for the next one
+						let job = match rx.lock().unwrap().recv() {
+							Ok(job) => job,
+							Err(_) => break, // The loader was dropped
+						};
+						if catch_unwind(AssertUnwindSafe(job)).is_err() {
+							eprintln!("A loading job panicked");
+						}
+						finished.fetch_add(1, Ordering::SeqCst);
+					})
+					.expect("Error spawning loader thread"),
+			);
+		}
+		Self {
+			jobs: Some(jobs),
+			workers,
+			requested: Arc::new(AtomicUsize::new(0)),
+			finished,
+		}
+	}
+
+	/// Runs `f` in a worker thread
+	pub fn spawn<T, F>(&self, f: F) -> LoadRequest<T>
+	where
+		T: Send + 'static,
+		F: FnOnce() -> T + Send + 'static,
+	{
+		let (tx, rx) = channel();
+		self.requested.fetch_add(1, Ordering::SeqCst);
+		self.jobs
+			.as_ref()
+			.unwrap()
+			.send(Box::new(move || {
+				// The request might have been dropped, nobody wants the result then
+				let _ = tx.send(f());
+			}))
+			.expect("Loader threads stopped");
+		LoadRequest {
+			rx,
+			result: None,
+			failed: false,
+		}
+	}
+
+	/// Fraction of the requested jobs that are finished, from 0 to 1
+	pub fn progress(&self) -> f32 {
+		let requested = self.requested.load(Ordering::SeqCst);
+		if requested == 0 {
+			1.0
+		} else {
+			self.finished.load(Ordering::SeqCst) as f32 / requested as f32
+		}
+	}
+
+	pub fn is_done(&self) -> bool {
+		self.finished.load(Ordering::SeqCst) >= self.requested.load(Ordering::SeqCst)
+	}
+}
+
+impl Drop for Loader {
+	fn drop(&mut self) {
+		// Closing the channel stops the workers once they finish the pending jobs
+		self.jobs = None;
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+/// The result of a job sent to a `Loader`
+pub struct LoadRequest<T> {
+	rx: Receiver<T>,
+	result: Option<T>,
+	failed: bool, // The job panicked, there won't be a result
+}
+
+#[allow(dead_code)]
+impl<T> LoadRequest<T> {
+	/// Checks if the job is finished, without blocking
+	pub fn is_ready(&mut self) -> bool {
+		if self.result.is_none() && !self.failed {
+			match self.rx.try_recv() {
+				Ok(res) => self.result = Some(res),
+				Err(TryRecvError::Empty) => (),
+				Err(TryRecvError::Disconnected) => self.failed = true,
+			}
+		}
+		self.result.is_some()
+	}
+
+	pub fn has_failed(&mut self) -> bool {
+		self.is_ready();
+		self.failed
+	}
+
+	/// Takes the result if the job is finished, without blocking
+	pub fn take(&mut self) -> Option<T> {
+		self.is_ready();
+		self.result.take()
+	}
+
+	/// Blocks until the job is finished. Returns `None` if the job panicked
+	pub fn wait(mut self) -> Option<T> {
+		if let Some(res) = self.result.take() {
+			return Some(res);
+		}
+		self.rx.recv().ok()
+	}
+}
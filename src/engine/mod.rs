@@ -9,18 +9,31 @@ pub mod hud;
 pub mod spatial;
 
 // Public mods
+pub mod assets;
+pub mod atlas;
+pub mod cook;
+pub mod error;
+pub mod loading;
 pub mod noise;
+pub mod pack;
 pub mod sound;
+pub mod stats;
 pub mod text;
 pub mod texture;
+pub mod validate;
+pub mod vfs;
 
 // Mods to re-export
 mod entity_registry;
 mod key_registry;
 mod utils;
+mod watcher;
 
+pub use assets::{AssetServer, AssetStats, Handle};
 pub use entity_registry::EntityRegistry;
+pub use error::{Error, Result};
 pub use key_registry::KeyRegistry;
+pub use stats::{FrameTimer, RenderStats};
 pub use utils::{depth_texture_to_color, FileLoader, RgbaColor};
 
 lazy_static! {
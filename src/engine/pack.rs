@@ -0,0 +1,255 @@
+// Shared between the engine and build.rs (included with #[path]), so it can only use the build dependencies
+
+use serde::{Deserialize, Serialize};
+
+use zip::CompressionMethod;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Path of the manifest inside a pack, relative to the assets root
+pub const MANIFEST_FILE: &str = "manifest.ron";
+
+/// Packing policy read by build.rs, relative to the crate root
+pub const POLICY_FILE: &str = "pack.ron";
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssetKind {
+	Image,
+	TextureData,
+	Model,
+	Material,
+	Font,
+	Sound,
+	Cooked, // Made by build.rs from another asset, see `cook`
+	Other,
+}
+
+impl AssetKind {
+	pub fn from_path<P: AsRef<Path>>(p: P) -> Self {
+		let ext = p
+			.as_ref()
+			.extension()
+			.and_then(|e| e.to_str())
+			.unwrap_or("")
+			.to_lowercase();
+		match ext.as_str() {
+			"png" | "jpg" | "jpeg" | "bmp" | "tga" | "hdr" => Self::Image,
+			"ron" => Self::TextureData,
+			"obj" => Self::Model,
+			"mtl" => Self::Material,
+			"ttf" | "otf" => Self::Font,
+			"wav" => Self::Sound,
+			"mesh" | "rgba" | "glyphs" => Self::Cooked,
+			_ => Self::Other,
+		}
+	}
+}
+
+/// How a file is stored inside a pack. The `FileLoader` decompresses all of them transparently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+	Stored,   // No compression, best for already compressed files (PNG) or fast loading
+	Deflated, // Fast to decompress
+	Bzip2,    // Smaller than deflate for big text and audio files, but slower to decompress
+}
+
+impl Default for Compression {
+	fn default() -> Self {
+		Self::Deflated
+	}
+}
+
+impl From<Compression> for CompressionMethod {
+	fn from(c: Compression) -> Self {
+		match c {
+			Compression::Stored => CompressionMethod::Stored,
+			Compression::Deflated => CompressionMethod::Deflated,
+			Compression::Bzip2 => CompressionMethod::Bzip2,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackRule {
+	pub pattern: String, // Glob, see `glob_match`
+	pub compression: Compression,
+}
+
+/// Chooses the compression of every file in a pack, the first rule that matches the path wins.
+/// Rules apply to the cooked files, not to the sources they replace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackPolicy {
+	#[serde(default)]
+	pub default: Compression,
+	#[serde(default)]
+	pub rules: Vec<PackRule>,
+	#[serde(default)]
+	pub font_sizes: Vec<f32>, // Pixel sizes to cook a glyph atlas for, for every font
+}
+
+#[allow(dead_code)]
+impl PackPolicy {
+	pub fn compression_for(&self, path: &str) -> Compression {
+		self.rules
+			.iter()
+			.find(|r| glob_match(&r.pattern, path))
+			.map(|r| r.compression)
+			.unwrap_or(self.default)
+	}
+}
+
+/// Matches a `/` separated path against a glob pattern.
+/// `*` matches any part of a name, `?` a single char and `**` any number of dirs.
+/// Patterns without a `/` are matched against the file name only, so `*.png` matches every PNG
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+	let path: Vec<&str> = path.split('/').collect();
+	if pattern.contains('/') {
+		let pattern: Vec<&str> = pattern.split('/').collect();
+		match_segments(&pattern, &path)
+	} else {
+		match_segment(
+			&pattern.chars().collect::<Vec<char>>(),
+			&path[path.len() - 1].chars().collect::<Vec<char>>(),
+		)
+	}
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+	match pattern.split_first() {
+		None => path.is_empty(),
+		Some((&"**", rest)) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+		Some((p, rest)) => match path.split_first() {
+			Some((s, path_rest)) => {
+				match_segment(
+					&p.chars().collect::<Vec<char>>(),
+					&s.chars().collect::<Vec<char>>(),
+				) && match_segments(rest, path_rest)
+			}
+			None => false,
+		},
+	}
+}
+
+fn match_segment(pattern: &[char], s: &[char]) -> bool {
+	match pattern.split_first() {
+		None => s.is_empty(),
+		Some(('*', rest)) => (0..=s.len()).any(|i| match_segment(rest, &s[i..])),
+		Some(('?', rest)) => !s.is_empty() && match_segment(rest, &s[1..]),
+		Some((c, rest)) => s.first() == Some(c) && match_segment(rest, &s[1..]),
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+	pub path: String, // Relative to the assets root, `/` separated
+	pub size: u64,
+	pub hash: u32, // CRC32 of the contents
+	pub kind: AssetKind,
+	#[serde(default)]
+	pub compression: Compression,
+}
+
+impl ManifestEntry {
+	pub fn new<T: ToString>(path: T, data: &[u8], compression: Compression) -> Self {
+		let path = path.to_string();
+		Self {
+			kind: AssetKind::from_path(&path),
+			path,
+			size: data.len() as u64,
+			hash: hash(data),
+			compression,
+		}
+	}
+}
+
+/// List of every file in a pack, written by build.rs and checked by the `FileLoader` when loading from the pack
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+	pub entries: BTreeMap<String, ManifestEntry>, // Sorted, so the same assets always give the same pack
+}
+
+#[allow(dead_code)]
+impl Manifest {
+	pub fn add(&mut self, entry: ManifestEntry) {
+		self.entries.insert(entry.path.clone(), entry);
+	}
+
+	pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+		self.entries.get(path)
+	}
+
+	/// Checks the contents of a file against its entry
+	pub fn verify(&self, path: &str, data: &[u8]) -> Result<(), IntegrityError> {
+		let entry = self
+			.get(path)
+			.ok_or_else(|| IntegrityError::NotInManifest(path.to_string()))?;
+		if entry.size != data.len() as u64 {
+			return Err(IntegrityError::SizeMismatch {
+				path: path.to_string(),
+				expected: entry.size,
+				found: data.len() as u64,
+			});
+		}
+		let found = hash(data);
+		if entry.hash != found {
+			return Err(IntegrityError::HashMismatch {
+				path: path.to_string(),
+				expected: entry.hash,
+				found,
+			});
+		}
+		Ok(())
+	}
+}
+
+pub fn hash(data: &[u8]) -> u32 {
+	crc32fast::hash(data)
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum IntegrityError {
+	Missing(String),                    // In the manifest, but not in the pack
+	NotInManifest(String),              // In the pack, but not in the manifest
+	Unreadable(String, std::io::Error), // In the pack, but it can't be decompressed
+	SizeMismatch {
+		path: String,
+		expected: u64,
+		found: u64,
+	},
+	HashMismatch {
+		path: String,
+		expected: u32,
+		found: u32,
+	},
+}
+
+impl std::fmt::Display for IntegrityError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		match self {
+			Self::Missing(path) => write!(f, "{} is missing from the pack", path),
+			Self::NotInManifest(path) => write!(f, "{} is not listed in the manifest", path),
+			Self::Unreadable(path, e) => write!(f, "{} is corrupt, it can't be read: {}", path, e),
+			Self::SizeMismatch {
+				path,
+				expected,
+				found,
+			} => write!(
+				f,
+				"{} is corrupt, expected {} bytes but found {} (truncated download?)",
+				path, expected, found
+			),
+			Self::HashMismatch {
+				path,
+				expected,
+				found,
+			} => write!(
+				f,
+				"{} is corrupt, expected hash {:08x} but found {:08x}",
+				path, expected, found
+			),
+		}
+	}
+}
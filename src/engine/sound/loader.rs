@@ -4,17 +4,33 @@ use hound::WavReader;
 
 use std::path::Path;
 
+use super::super::assets::Asset;
+use super::super::error::{Error, Result};
 use super::super::FileLoader;
 use super::SOUNDS_PATH;
 
-pub fn load_wav<P: AsRef<Path>>(file_loader: &mut FileLoader, filename: P) -> SamplesBuffer<f32> {
+pub fn load_wav<P: AsRef<Path>>(
+	file_loader: &mut FileLoader,
+	filename: P,
+) -> Result<SamplesBuffer<f32>> {
+	let filename = filename.as_ref();
 	let mut reader =
-		WavReader::new(file_loader.load(filename).expect("Error loading wav file")).unwrap();
+		WavReader::new(file_loader.load(filename)?).map_err(|e| Error::decode(filename, e))?;
 	let samples = reader
 		.samples::<i16>() // Load samples as i16 iter
 		.map(|x| if let Ok(v) = x { Some(v as f32) } else { None }) // Map them as an Option<f32> iteer
 		.filter(|x| x.is_some()) // Filter None samples
 		.map(|x| x.unwrap()) // Unwrap from option the rest of the samples
 		.collect::<Vec<f32>>(); // Collect as a Vec<f32>
-	SamplesBuffer::new(reader.spec().channels, reader.spec().sample_rate, samples)
+	Ok(SamplesBuffer::new(
+		reader.spec().channels,
+		reader.spec().sample_rate,
+		samples,
+	))
+}
+
+impl Asset for SamplesBuffer<f32> {
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self> {
+		load_wav(file_loader, path)
+	}
 }
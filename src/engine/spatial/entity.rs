@@ -18,7 +18,7 @@ pub struct Entity {
 }
 
 impl Entity {
-	pub fn new<'p, C: GraphicsContext>(surface: &mut C, obj: Obj) -> Self {
+	pub fn new<'p, C: GraphicsContext>(surface: &mut C, obj: &Obj) -> Self {
 		Self {
 			tess: obj.to_tess(surface),
 			pos: Point3::origin(),
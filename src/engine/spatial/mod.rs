@@ -13,7 +13,7 @@ pub mod obj;
 mod renderer;
 
 //pub use entity::Entity;
-pub use renderer::Renderer;
+pub use renderer::{PendingMeshes, Renderer};
 
 #[derive(UniformInterface)]
 pub struct SpatialUniformInterface {
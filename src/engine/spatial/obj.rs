@@ -1,13 +1,15 @@
 use luminance::context::GraphicsContext;
 use luminance::tess::{Mode as TessMode, Tess, TessBuilder};
 
-use wavefront_obj::{mtl, obj};
+use wavefront_obj::mtl;
 
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read as _;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use super::super::assets::Asset;
+use super::super::cook::{self, CookedMesh};
+use super::super::error::{Error, Result};
 use super::super::{FileLoader, MODELS_PATH};
 use super::{Vertex, VertexIndex, VertexNormal, VertexPosition};
 
@@ -76,152 +78,147 @@ pub struct Obj {
 	pub geometries: Vec<Geometry>,
 }
 
+impl Asset for Obj {
+	// The path includes MODELS_PATH, like every other asset path
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self> {
+		Obj::load(
+			file_loader,
+			path.strip_prefix(&*MODELS_PATH).unwrap_or(path),
+		)
+	}
+}
+
 impl Obj {
-	pub fn to_tess<C>(self, ctx: &mut C) -> Vec<(Tess, Material)>
+	pub fn to_tess<C>(&self, ctx: &mut C) -> Vec<(Tess, Material)>
 	where
 		C: GraphicsContext,
 	{
 		let mut res = Vec::new();
-		for geo in self.geometries {
+		for geo in &self.geometries {
 			if let Ok(t) = TessBuilder::new(ctx)
 				.set_mode(TessMode::Triangle)
-				.add_vertices(geo.vertices)
-				.set_indices(geo.indices)
+				.add_vertices(&geo.vertices)
+				.set_indices(&geo.indices)
 				.build()
 			{
-				res.push((t, geo.material));
+				res.push((t, geo.material.clone()));
 			}
 		}
 
 		return res;
 	}
 
-	pub fn load<P>(file_loader: &mut FileLoader, path: P) -> Result<Self, String>
+	pub fn load<P>(file_loader: &mut FileLoader, path: P) -> Result<Self>
 	where
 		P: AsRef<Path>,
 	{
-		let file_content = {
-			let mut file = file_loader
-				.load(MODELS_PATH.join(&path))
-				.or_else(|| {
-					panic!("Can't open file: {:?}", MODELS_PATH.join(path));
-				})
-				.unwrap();
-			let mut content = String::new();
-			file.read_to_string(&mut content).unwrap();
-			content
-		};
-		let obj_set = obj::parse(file_content).map_err(|e| format!("cannot parse: {:?}", e))?;
-
-		let mtl = if let Some(mtl_lib) = obj_set.material_library {
-			let mut file = file_loader
-				.load(MODELS_PATH.join(&mtl_lib))
-				.or_else(|| {
-					panic!("Can't open file: {:?}", MODELS_PATH.join(mtl_lib));
-				})
-				.unwrap();
-			let mut content = String::new();
-			file.read_to_string(&mut content).unwrap();
-			Some(mtl::parse(content).map_err(|e| format!("cannot parse: {:?}", e))?)
-		} else {
-			None
-		};
+		ObjSource::read(file_loader, path)?.parse()
+	}
+}
 
-		println!("{:?}", mtl);
-		let objects = obj_set.objects;
+/// An OBJ file and its material library (or its cooked mesh), read on the main thread so it can be parsed in another one
+#[derive(Debug, Clone)]
+pub enum ObjSource {
+	Text {
+		obj: (PathBuf, String),
+		mtl: Option<(PathBuf, String)>,
+	},
+	Cooked(PathBuf, Vec<u8>),
+}
 
-		assert_eq!(objects.len(), 1, "expecting a single object");
+impl ObjSource {
+	pub fn read<P>(file_loader: &mut FileLoader, path: P) -> Result<Self>
+	where
+		P: AsRef<Path>,
+	{
+		let obj_path = MODELS_PATH.join(path);
+		if let Some(cooked_path) = file_loader.find_cooked(&obj_path, cook::MESH_EXT) {
+			let mut data = Vec::new();
+			file_loader
+				.load(&cooked_path)?
+				.read_to_end(&mut data)
+				.map_err(|e| Error::Io(cooked_path.clone(), e))?;
+			return Ok(ObjSource::Cooked(cooked_path, data));
+		}
 
-		let object = objects.into_iter().next().unwrap();
+		let obj = read_to_string(file_loader, &obj_path)?;
+		let mtl = if let Some((line, mtl_lib)) = cook::mtl_lib(&obj) {
+			let mtl_path = cook::mtl_path(&obj_path, &mtl_lib);
+			let content = read_to_string(file_loader, &mtl_path).map_err(|e| match e {
+				Error::NotFound(_) => Error::parse(
+					&obj_path,
+					Some(line),
+					format!("material library {:?} not found", mtl_path),
+				),
+				e => e,
+			})?;
+			Some((mtl_path, content))
+		} else {
+			None
+		};
+		Ok(ObjSource::Text {
+			obj: (obj_path, obj),
+			mtl,
+		})
+	}
 
-		// verify!(object.geometry.len() == 1).ok_or("expecting a single geometry".to_owned())?;
-		let mut geos = Vec::new();
-		for geometry in object.geometry {
-			let g_material = if let Some(mtl_lib) = &mtl {
-				if let Some(mtl_name) = geometry.material_name {
-					let mut res = None;
-					for material in &mtl_lib.materials {
-						if material.name == mtl_name {
-							res = Some(material.clone())
-						}
+	/// Parses the OBJ & MTL files or decodes the cooked mesh, doesn't need the GPU or the `FileLoader`
+	pub fn parse(self) -> Result<Obj> {
+		let mesh = match self {
+			ObjSource::Text {
+				obj: (obj_path, obj),
+				mtl,
+			} => {
+				let mtl_path = mtl.as_ref().map(|(p, _)| p.clone());
+				CookedMesh::from_obj(obj, mtl.map(|(_, content)| content)).map_err(|e| {
+					let path = if e.in_mtl {
+						mtl_path.unwrap_or(obj_path)
+					} else {
+						obj_path
+					};
+					if e.line.is_some() {
+						Error::parse(path, e.line, e.msg)
+					} else {
+						Error::invalid(path, e.msg)
 					}
-					res
-				} else {
-					None
-				}
-			} else {
-				None
+				})?
 			}
-			.unwrap_or(Material {
-				name: "Default".into(),
-				specular_coefficient: 225.0,
-				color_ambient: mtl::Color {
-					r: 1.0,
-					g: 1.0,
-					b: 1.0,
-				},
-				color_diffuse: mtl::Color {
-					r: 0.001174,
-					g: 0.0,
-					b: 0.8,
-				},
-				color_specular: mtl::Color {
-					r: 0.5,
-					g: 0.5,
-					b: 0.5,
-				},
-				color_emissive: Some(mtl::Color {
-					r: 0.0,
-					g: 0.0,
-					b: 0.0,
-				}),
-				optical_density: Some(1.45),
-				alpha: 1.0,
-				illumination: mtl::Illumination::AmbientDiffuseSpecular,
-				uv_map: None,
-			});
-
-			println!("loading {}", object.name);
-			println!("{} vertices", object.vertices.len());
-			println!("{} shapes", geometry.shapes.len());
-
-			// build up vertices; for this to work, we remove duplicated vertices by putting them in a
-			// map associating the vertex with its ID
-			let mut vertex_cache: HashMap<obj::VTNIndex, VertexIndex> = HashMap::new();
-			let mut vertices: Vec<Vertex> = Vec::new();
-			let mut indices: Vec<VertexIndex> = Vec::new();
-
-			for shape in geometry.shapes {
-				if let obj::Primitive::Triangle(a, b, c) = shape.primitive {
-					for key in &[a, b, c] {
-						if let Some(vertex_index) = vertex_cache.get(key) {
-							indices.push(*vertex_index);
-						} else {
-							let p = object.vertices[key.0];
-							let n = object.normals
-								[key.2.ok_or("missing normal for a vertex".to_owned())?];
-							let position =
-								VertexPosition::new([p.x as f32, p.y as f32, p.z as f32]);
-							let normal = VertexNormal::new([n.x as f32, n.y as f32, n.z as f32]);
-							let vertex = Vertex { position, normal };
-							let vertex_index = vertices.len() as VertexIndex;
-
-							vertex_cache.insert(*key, vertex_index);
-							vertices.push(vertex);
-							indices.push(vertex_index);
-						}
-					}
-				} else {
-					return Err("unsupported non-triangle shape".to_owned());
-				}
+			ObjSource::Cooked(path, data) => {
+				cook::decode::<CookedMesh>(&data).map_err(|e| Error::decode(path, e))?
 			}
+		};
+		Ok(Obj::from(mesh))
+	}
+}
 
-			geos.push(Geometry {
-				vertices,
-				indices,
-				material: g_material,
+impl From<CookedMesh> for Obj {
+	fn from(mesh: CookedMesh) -> Self {
+		let geometries = mesh
+			.geometries
+			.into_iter()
+			.map(|geo| Geometry {
+				vertices: geo
+					.positions
+					.iter()
+					.zip(geo.normals.iter())
+					.map(|(p, n)| Vertex {
+						position: VertexPosition::new(*p),
+						normal: VertexNormal::new(*n),
+					})
+					.collect(),
+				indices: geo.indices,
+				material: geo.material.into(),
 			})
-		}
-		Ok(Obj { geometries: geos })
+			.collect();
+		Obj { geometries }
 	}
 }
+
+fn read_to_string(file_loader: &mut FileLoader, path: &Path) -> Result<String> {
+	let mut content = String::new();
+	file_loader
+		.load(path)?
+		.read_to_string(&mut content)
+		.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+	Ok(content)
+}
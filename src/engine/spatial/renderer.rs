@@ -9,14 +9,36 @@ use cgmath::Vector3;
 
 use std::path::Path;
 
-use super::super::FileLoader;
+use super::super::assets::AssetServer;
+use super::super::error::{Error, Result};
+use super::super::loading::{LoadRequest, Loader};
+use super::super::stats::RenderStats;
+use super::super::{FileLoader, MODELS_PATH};
 use super::depth;
-use super::{camera::Camera, entity::Entity, obj::Obj, SpatialUniformInterface, VertexSemantics};
+use super::obj::{Obj, ObjSource};
+use super::{camera::Camera, entity::Entity, SpatialUniformInterface, VertexSemantics};
 use crate::terrain;
 
 const VS_STR: &str = include_str!("shaders/vs.glsl");
 const FS_STR: &str = include_str!("shaders/fs.glsl");
 
+const MESH_FILE: &str = "test2.obj";
+
+/// Meshes of the renderer being generated & parsed in the background
+pub struct PendingMeshes {
+	terrain: LoadRequest<Obj>,
+	mesh: LoadRequest<Result<Obj>>,
+}
+
+impl PendingMeshes {
+	pub fn is_ready(&mut self) -> bool {
+		// Both have to be checked, so the results are stored
+		let terrain = self.terrain.is_ready() || self.terrain.has_failed();
+		let mesh = self.mesh.is_ready() || self.mesh.has_failed();
+		terrain && mesh
+	}
+}
+
 pub struct Renderer {
 	program: Program<VertexSemantics, (), SpatialUniformInterface>,
 	depth_program: Program<depth::VertexSemantics, (), depth::UniformInterface>,
@@ -25,14 +47,69 @@ pub struct Renderer {
 	pub depth_camera: Camera,
 	pub terrain: Entity, //Vec<(Tess, Material)>,
 	pub mesh: Entity,    //Vec<(Tess, Material)>,
+	stats: RenderStats,
 }
 
 impl Renderer {
 	pub fn new<C: GraphicsContext>(
+		assets: &mut AssetServer,
 		file_loader: &mut FileLoader,
 		surface: &mut C,
 		size: [u32; 2],
 		depth_map_size: [u32; 2],
+	) -> Result<Self> {
+		let mesh = assets.load::<Obj>(file_loader, &MODELS_PATH.join(MESH_FILE))?;
+		Ok(Self::with_meshes(
+			surface,
+			size,
+			depth_map_size,
+			&terrain::generate(1000, 1000),
+			&mesh,
+		))
+	}
+
+	/// Starts generating the terrain and parsing the mesh in the loader threads
+	pub fn load_meshes(loader: &Loader, file_loader: &mut FileLoader) -> Result<PendingMeshes> {
+		let source = ObjSource::read(file_loader, Path::new(MESH_FILE))?;
+		Ok(PendingMeshes {
+			terrain: loader.spawn(|| terrain::generate(1000, 1000)),
+			mesh: loader.spawn(move || source.parse()),
+		})
+	}
+
+	/// Uploads the meshes loaded in the background, blocking if they aren't ready yet
+	pub fn from_meshes<C: GraphicsContext>(
+		assets: &mut AssetServer,
+		surface: &mut C,
+		size: [u32; 2],
+		depth_map_size: [u32; 2],
+		meshes: PendingMeshes,
+	) -> Result<Self> {
+		let mesh_path = MODELS_PATH.join(MESH_FILE);
+		let terrain = meshes
+			.terrain
+			.wait()
+			.ok_or_else(|| Error::decode("terrain", "the generation thread panicked"))?;
+		let mesh = meshes
+			.mesh
+			.wait()
+			.ok_or_else(|| Error::decode(&mesh_path, "the loading thread panicked"))??;
+		let mesh = assets.insert(&mesh_path, mesh);
+		Ok(Self::with_meshes(
+			surface,
+			size,
+			depth_map_size,
+			&terrain,
+			&mesh,
+		))
+	}
+
+	fn with_meshes<C: GraphicsContext>(
+		surface: &mut C,
+		size: [u32; 2],
+		depth_map_size: [u32; 2],
+		terrain: &Obj,
+		mesh: &Obj,
 	) -> Self {
 		let program: Program<VertexSemantics, (), SpatialUniformInterface> =
 			Program::from_strings(None, VS_STR, None, FS_STR)
@@ -53,14 +130,25 @@ impl Renderer {
 			render_st,
 			camera: Camera::new(size),
 			depth_camera: Camera::new(depth_map_size),
-			terrain: Entity::new(surface, terrain::generate(1000, 1000)),
-			mesh: Entity::new(
-				surface,
-				Obj::load(file_loader, Path::new("test2.obj")).unwrap(),
-			),
+			terrain: Entity::new(surface, terrain),
+			mesh: Entity::new(surface, mesh),
+			stats: RenderStats::default(),
 		}
 	}
 
+	/// Loads the mesh again from its OBJ file, keeping its transform
+	pub fn reload_mesh<C: GraphicsContext>(
+		&mut self,
+		assets: &mut AssetServer,
+		file_loader: &mut FileLoader,
+		surface: &mut C,
+	) -> Result<()> {
+		let obj = Obj::load(file_loader, Path::new(MESH_FILE))?;
+		self.mesh.tess = obj.to_tess(surface);
+		assets.insert(&MODELS_PATH.join(MESH_FILE), obj);
+		Ok(())
+	}
+
 	pub fn render<C: GraphicsContext>(
 		&mut self,
 		shd_gate: &mut ShadingGate<'_, C>,
@@ -82,6 +170,7 @@ impl Renderer {
 				self.mesh.render(pipeline, &iface, &mut tess_gate, size);
 			});
 		});
+		self.stats += self.entity_stats();
 		//self.mesh.rot_x += Rad(0.01).into();
 		//self.mesh.rot_y += Rad(0.01).into();
 		// self.mesh.pos += Vector3::new(0.,0.01,0.);
@@ -132,5 +221,20 @@ impl Renderer {
 				}
 			});
 		});
+		self.stats += self.entity_stats();
+	}
+
+	// One draw call per material of each entity
+	fn entity_stats(&self) -> RenderStats {
+		RenderStats {
+			draw_calls: self.terrain.tess.len() + self.mesh.tess.len(),
+			entities: 2,
+			uploads: 0,
+		}
+	}
+
+	/// What was drawn since the last call, with the shadows
+	pub fn take_stats(&mut self) -> RenderStats {
+		std::mem::take(&mut self.stats)
 	}
 }
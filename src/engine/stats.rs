@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::ops::{Add, AddAssign};
+use std::time::Instant;
+
+// Frames kept by the `FrameTimer`, enough for the graph of the performance overlay
+const FRAMES: usize = 120;
+
+/// What a renderer drew since its stats were last taken
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+	pub draw_calls: usize,
+	pub entities: usize, // Entities (or meshes) drawn
+	pub uploads: usize,  // Vertex buffers uploaded again because something changed
+}
+
+impl Add for RenderStats {
+	type Output = Self;
+
+	fn add(self, other: Self) -> Self {
+		Self {
+			draw_calls: self.draw_calls + other.draw_calls,
+			entities: self.entities + other.entities,
+			uploads: self.uploads + other.uploads,
+		}
+	}
+}
+
+impl AddAssign for RenderStats {
+	fn add_assign(&mut self, other: Self) {
+		*self = *self + other
+	}
+}
+
+/// Measures the time between frames, keeping the last ones
+pub struct FrameTimer {
+	last: Instant,
+	times: VecDeque<f32>, // In seconds, the oldest first
+}
+
+#[allow(dead_code)]
+impl FrameTimer {
+	pub fn new() -> Self {
+		Self {
+			last: Instant::now(),
+			times: VecDeque::with_capacity(FRAMES),
+		}
+	}
+
+	/// Call it once per frame, returns the seconds since the last call
+	pub fn tick(&mut self) -> f32 {
+		let now = Instant::now();
+		let dt = (now - self.last).as_secs_f32();
+		self.last = now;
+		if self.times.len() == FRAMES {
+			self.times.pop_front();
+		}
+		self.times.push_back(dt);
+		dt
+	}
+
+	/// Time of the last frame in seconds, 0 before the first tick
+	pub fn last(&self) -> f32 {
+		self.times.back().copied().unwrap_or(0.0)
+	}
+
+	/// Times of the last frames in seconds, the oldest first
+	pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+		self.times.iter().copied()
+	}
+
+	/// Average frame time of the last frames, in seconds
+	pub fn average(&self) -> f32 {
+		if self.times.is_empty() {
+			0.0
+		} else {
+			self.times.iter().sum::<f32>() / self.times.len() as f32
+		}
+	}
+
+	/// Longest of the last frames, in seconds
+	pub fn max(&self) -> f32 {
+		self.times.iter().copied().fold(0.0, f32::max)
+	}
+
+	/// Frames per second, from the average frame time
+	pub fn fps(&self) -> f32 {
+		let average = self.average();
+		if average > 0.0 {
+			1.0 / average
+		} else {
+			0.0
+		}
+	}
+}
@@ -10,6 +10,9 @@ use lazy_static::lazy_static;
 use std::cmp::PartialEq;
 use std::path::Path;
 
+use super::assets::{Asset, AssetServer};
+use super::cook::{self, CookedFont};
+use super::error::{Error, Result};
 use super::FileLoader;
 use super::RgbaColor;
 use super::FONTS_PATH;
@@ -72,6 +75,24 @@ impl Font {
 	}
 }
 
+impl Asset for RTFont<'static> {
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self> {
+		let mut font_data = Vec::new();
+		file_loader
+			.load(path)?
+			.read_to_end(&mut font_data)
+			.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+		RTFont::try_from_vec(font_data).ok_or_else(|| Error::decode(path, "not a valid font"))
+	}
+}
+
+impl Asset for CookedFont {
+	fn load(file_loader: &mut FileLoader, path: &Path) -> Result<Self> {
+		let data = file_loader.load(path)?.into_inner();
+		cook::decode(&data).map_err(|e| Error::decode(path, e))
+	}
+}
+
 // https://www.freetype.org/freetype2/docs/tutorial/metrics.png
 #[derive(Debug, Clone, Copy)]
 pub struct GlyphMetrics {
@@ -110,127 +131,143 @@ fn draw_map(map: Vec<Vec<u8>>) {
 	println!("+");
 }
 
+// Rasterizes a string with the TTF font, for the chars or sizes without a cooked atlas
+fn rasterize(rt_font: &RTFont<'static>, text: &str, size: f32) -> Vec<Vec<u8>> {
+	// Desired font pixel height
+	let height: f32 = size; // to get 80 chars across (fits most terminals); adjust as desired
+	let pixel_height = height.ceil() as usize;
+
+	let scale = Scale {
+		x: height,
+		y: height,
+	};
+
+	// The origin of a line of text is at the baseline (roughly where
+	// non-descending letters sit). We don't want to clip the text, so we shift
+	// it down with an offset when laying it out. v_metrics.ascent is the
+	// distance between the baseline and the highest edge of any glyph in
+	// the font. That's enough to guarantee that there's no clipping.
+	let v_metrics = rt_font.v_metrics(scale);
+	let offset = point(0.0, v_metrics.ascent);
+
+	// Glyphs to draw for "RustType". Feel free to try other strings.
+	let glyphs: Vec<_> = rt_font.layout(text, scale, offset).collect();
+
+	// Find the most visually pleasing width to display
+	let width = glyphs
+		.iter()
+		.rev()
+		.map(|g| g.position().x as f32 + g.unpositioned().h_metrics().advance_width)
+		.next()
+		.unwrap_or(0.0)
+		.ceil() as usize;
+
+	// println!("width: {}, height: {}", width, pixel_height);
+
+	// Rasterise directly into ASCII art.
+	//let mut pixel_data = vec![b' '; width * pixel_height];
+	let mut map: Vec<Vec<u8>> = Vec::new();
+	let mut empty_row = Vec::new();
+	empty_row.resize(width, 0);
+	map.resize(pixel_height, empty_row);
+	//let mapping = b"@%#x+=:-. "; // The approximation of greyscale
+	//let mapping_scale = (mapping.len() - 1) as f32;
+	for g in glyphs {
+		if let Some(bb) = g.pixel_bounding_box() {
+			g.draw(|x, y, v| {
+				// v should be in the range 0.0 to 1.0
+				//let i = ((v * -1. + 1.) * mapping_scale + 0.5) as usize;
+				// so something's wrong if you get $ in the output.
+				//let c = mapping.get(i).cloned().unwrap_or(b'$');
+				let x = x as i32 + bb.min.x;
+				let y = y as i32 + bb.min.y;
+				// There's still a possibility that the glyph clips the boundaries of the bitmap
+				if x >= 0 && x < width as i32 && y >= 0 && y < pixel_height as i32 {
+					let x = x as usize;
+					let y = y as usize;
+					//pixel_data[(x + y * width)] = c;
+					map[y][x] = (v * 255.) as u8
+				}
+			})
+		}
+	}
+	map
+}
+
 pub fn tex_from_string<T: ToString, C: GraphicsContext>(
+	assets: &mut AssetServer,
 	file_loader: &mut FileLoader,
 	surface: &mut C,
 	name: T,
 	font: &Font,
-) -> Option<(Texture<Dim2, NormRGBA8UI>, [[u32; 2]; 4])> {
-	let mut font_data = Vec::new();
-	if let Some(mut f) = file_loader.load(FONTS_PATH.join(font.name())) {
-		f.read_to_end(&mut font_data).expect("Error loading data");
+) -> Result<(Texture<Dim2, NormRGBA8UI>, [[u32; 2]; 4])> {
+	let text = name.to_string();
+	let font_path = FONTS_PATH.join(font.name());
+	let pixel_height = font.size.ceil() as usize;
 
-		let rt_font = RTFont::try_from_bytes(font_data.as_slice())
-			.expect("error constructing a Font from bytes");
+	// Use the glyph atlas cooked by build.rs if there's one at this size
+	let cooked = match file_loader.find_cooked(&font_path, &cook::glyphs_ext(font.size)) {
+		Some(atlas_path) => assets
+			.load::<CookedFont>(file_loader, &atlas_path)?
+			.render(&text),
+		None => None,
+	};
+	let map = match cooked {
+		Some(map) => map,
+		None => rasterize(
+			&*assets.load::<RTFont<'static>>(file_loader, &font_path)?,
+			&text,
+			font.size,
+		),
+	};
 
-		// Desired font pixel height
-		let height: f32 = font.size; // to get 80 chars across (fits most terminals); adjust as desired
-		let pixel_height = height.ceil() as usize;
+	// draw_map(map.clone());
 
-		let scale = Scale {
-			x: height,
-			y: height,
-		};
+	// Print it out
+	// let stdout = ::std::io::stdout();
+	// let mut handle = stdout.lock();
+	// for j in 0..pixel_height {
+	// 	handle
+	// 		.write_all(&pixel_data[j * width..(j + 1) * width])
+	// 		.unwrap();
+	// 	handle.write_all(b"\n").unwrap();
+	// }
 
-		// The origin of a line of text is at the baseline (roughly where
-		// non-descending letters sit). We don't want to clip the text, so we shift
-		// it down with an offset when laying it out. v_metrics.ascent is the
-		// distance between the baseline and the highest edge of any glyph in
-		// the font. That's enough to guarantee that there's no clipping.
-		let v_metrics = rt_font.v_metrics(scale);
-		let offset = point(0.0, v_metrics.ascent);
-
-		// Glyphs to draw for "RustType". Feel free to try other strings.
-		let glyphs: Vec<_> = rt_font.layout(&name.to_string(), scale, offset).collect();
-
-		// Find the most visually pleasing width to display
-		let width = glyphs
-			.iter()
-			.rev()
-			.map(|g| g.position().x as f32 + g.unpositioned().h_metrics().advance_width)
-			.next()
-			.unwrap_or(0.0)
-			.ceil() as usize;
-
-		// println!("width: {}, height: {}", width, pixel_height);
-
-		// Rasterise directly into ASCII art.
-		//let mut pixel_data = vec![b' '; width * pixel_height];
-		let mut map: Vec<Vec<u8>> = Vec::new();
-		let mut empty_row = Vec::new();
-		empty_row.resize(width, 0);
-		map.resize(pixel_height, empty_row);
-		//let mapping = b"@%#x+=:-. "; // The approximation of greyscale
-		//let mapping_scale = (mapping.len() - 1) as f32;
-		for g in glyphs {
-			if let Some(bb) = g.pixel_bounding_box() {
-				g.draw(|x, y, v| {
-					// v should be in the range 0.0 to 1.0
-					//let i = ((v * -1. + 1.) * mapping_scale + 0.5) as usize;
-					// so something's wrong if you get $ in the output.
-					//let c = mapping.get(i).cloned().unwrap_or(b'$');
-					let x = x as i32 + bb.min.x;
-					let y = y as i32 + bb.min.y;
-					// There's still a possibility that the glyph clips the boundaries of the bitmap
-					if x >= 0 && x < width as i32 && y >= 0 && y < pixel_height as i32 {
-						let x = x as usize;
-						let y = y as usize;
-						//pixel_data[(x + y * width)] = c;
-						map[y][x] = (v * 255.) as u8
-					}
-				})
-			}
+	let mut texels: Vec<u8> = Vec::new();
+	texels.resize(map.len() * map[0].len() * 4, 0_u8);
+	let height = map.len();
+	let width = map[0].len();
+	for i in 0..height {
+		for j in 0..width {
+			let val = map[i][j];
+			texels[i * width * 4 + j * 4 + 0] = font.color.r; // R: This could be changed for the desired color
+			texels[i * width * 4 + j * 4 + 1] = font.color.g; // G: This could be changed for the desired color
+			texels[i * width * 4 + j * 4 + 2] = font.color.b; // B: This could be changed for the desired color
+			texels[i * width * 4 + j * 4 + 3] = (font.color.a as f64 / 255. * val as f64) as u8;
+			// A: This could be multiplied times the alpha multiplier
 		}
+	}
 
-		// draw_map(map.clone());
-
-		// Print it out
-		// let stdout = ::std::io::stdout();
-		// let mut handle = stdout.lock();
-		// for j in 0..pixel_height {
-		// 	handle
-		// 		.write_all(&pixel_data[j * width..(j + 1) * width])
-		// 		.unwrap();
-		// 	handle.write_all(b"\n").unwrap();
-		// }
-
-		let mut texels: Vec<u8> = Vec::new();
-		texels.resize(map.len() * map[0].len() * 4, 0_u8);
-		let height = map.len();
-		let width = map[0].len();
-		for i in 0..height {
-			for j in 0..width {
-				let val = map[i][j];
-				texels[i * width * 4 + j * 4 + 0] = font.color.r; // R: This could be changed for the desired color
-				texels[i * width * 4 + j * 4 + 1] = font.color.g; // G: This could be changed for the desired color
-				texels[i * width * 4 + j * 4 + 2] = font.color.b; // B: This could be changed for the desired color
-				texels[i * width * 4 + j * 4 + 3] = (font.color.a as f64 / 255. * val as f64) as u8;
-				// A: This could be multiplied times the alpha multiplier
-			}
-		}
+	let res_tex: Texture<Dim2, NormRGBA8UI> = Texture::new(
+		surface,
+		[width as u32, pixel_height as u32],
+		0,
+		Sampler::default(),
+	)
+	.map_err(|e| Error::upload(None, e))?;
 
-		let res_tex: Texture<Dim2, NormRGBA8UI> = Texture::new(
-			surface,
-			[width as u32, pixel_height as u32],
-			0,
-			Sampler::default(),
-		)
-		.expect("Error creating texture");
-
-		res_tex
-			.upload_raw(GenMipmaps::No, &texels)
-			.expect("Error uploading texture");
-		// 3 2
-		// 0 1 (UV indices)
-		return Some((
-			res_tex,
-			[
-				[0, height as u32],
-				[width as u32, height as u32],
-				[width as u32, 0],
-				[0, 0],
-			],
-		));
-	}
-	None
+	res_tex
+		.upload_raw(GenMipmaps::No, &texels)
+		.map_err(|e| Error::upload(None, e))?;
+	// 3 2
+	// 0 1 (UV indices)
+	return Ok((
+		res_tex,
+		[
+			[0, height as u32],
+			[width as u32, height as u32],
+			[width as u32, 0],
+			[0, 0],
+		],
+	));
 }
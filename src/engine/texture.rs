@@ -1,35 +1,428 @@
-use serde::Deserialize;
+use luminance::pixel::{NormR16UI, NormR8UI, NormRGBA16UI, NormRGBA8UI, RGB32F, SRGBA8UI};
+use luminance::texture::{Dim2, MagFilter, MinFilter, Sampler, Texture, Wrap as GlWrap};
+
+use serde::{Deserialize, Serialize};
 
 use ron::de::from_reader;
 
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use super::error::{Error, Result};
 use super::FileLoader;
 use super::TEXTURES_PATH;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Filter {
+	Nearest, // Sharp pixels, for pixel art
+	Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Wrap {
+	Clamp,
+	Repeat,
+	MirroredRepeat,
+}
+
+impl From<Wrap> for GlWrap {
+	fn from(w: Wrap) -> Self {
+		match w {
+			Wrap::Clamp => GlWrap::ClampToEdge,
+			Wrap::Repeat => GlWrap::Repeat,
+			Wrap::MirroredRepeat => GlWrap::MirroredRepeat,
+		}
+	}
+}
+
+/// Pixel format of the texture on the GPU, the image is converted to it when loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextureFormat {
+	Rgba8,  // 8-bit color, sampled as it is stored
+	Srgba8, // 8-bit color in sRGB, converted to linear when sampled (the HUD shaders encode it back)
+	R8,     // Single channel, for masks
+	R16,    // Single 16-bit channel, for heightmaps
+	Rgba16, // 16-bit color
+	RgbF32, // Floating point color, for HDR images like environment maps
+	Source, // Whichever of the above is closest to the format of the image file
+}
+
+impl Default for TextureFormat {
+	fn default() -> Self {
+		Self::Rgba8
+	}
+}
+
+/// A texture in any of the `TextureFormat`s
+pub enum AnyTexture {
+	Rgba8(Texture<Dim2, NormRGBA8UI>),
+	Srgba8(Texture<Dim2, SRGBA8UI>),
+	R8(Texture<Dim2, NormR8UI>),
+	R16(Texture<Dim2, NormR16UI>),
+	Rgba16(Texture<Dim2, NormRGBA16UI>),
+	RgbF32(Texture<Dim2, RGB32F>),
+}
+
+#[allow(dead_code)]
+impl AnyTexture {
+	pub fn size(&self) -> [u32; 2] {
+		match self {
+			Self::Rgba8(tex) => tex.size(),
+			Self::Srgba8(tex) => tex.size(),
+			Self::R8(tex) => tex.size(),
+			Self::R16(tex) => tex.size(),
+			Self::Rgba16(tex) => tex.size(),
+			Self::RgbF32(tex) => tex.size(),
+		}
+	}
+
+	/// Never `TextureFormat::Source`, that one is resolved when the image is loaded
+	pub fn format(&self) -> TextureFormat {
+		match self {
+			Self::Rgba8(_) => TextureFormat::Rgba8,
+			Self::Srgba8(_) => TextureFormat::Srgba8,
+			Self::R8(_) => TextureFormat::R8,
+			Self::R16(_) => TextureFormat::R16,
+			Self::Rgba16(_) => TextureFormat::Rgba16,
+			Self::RgbF32(_) => TextureFormat::RgbF32,
+		}
+	}
+
+	/// Memory used by the texels of the base level, without the mipmaps
+	pub fn bytes(&self) -> usize {
+		let texel = match self {
+			Self::Rgba8(_) | Self::Srgba8(_) => 4,
+			Self::R8(_) => 1,
+			Self::R16(_) => 2,
+			Self::Rgba16(_) => 8,
+			Self::RgbF32(_) => 12,
+		};
+		let [w, h] = self.size();
+		w as usize * h as usize * texel
+	}
+}
+
+impl From<Texture<Dim2, NormRGBA8UI>> for AnyTexture {
+	fn from(tex: Texture<Dim2, NormRGBA8UI>) -> Self {
+		Self::Rgba8(tex)
+	}
+}
+
+/// How a texture is sampled, set in the `TextureData` RON. The default is linear, clamped and without mipmaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TextureSettings {
+	#[serde(default = "linear")]
+	pub min_filter: Filter,
+	#[serde(default = "linear")]
+	pub mag_filter: Filter,
+	#[serde(default = "clamp")]
+	pub wrap: Wrap,
+	#[serde(default)]
+	pub mipmaps: bool, // Generated when the texture is uploaded
+	#[serde(default = "linear")]
+	pub mipmap_filter: Filter, // Between mipmap levels, linear with linear filters is trilinear filtering
+	// luminance 0.39 samplers don't have anisotropy, so anything but `None` is rejected
+	#[serde(default)]
+	pub anisotropy: Option<u32>,
+	#[serde(default)]
+	pub format: TextureFormat,
+}
+
+fn linear() -> Filter {
+	Filter::Linear
+}
+
+fn clamp() -> Wrap {
+	Wrap::Clamp
+}
+
+impl Default for TextureSettings {
+	fn default() -> Self {
+		Self {
+			min_filter: Filter::Linear,
+			mag_filter: Filter::Linear,
+			wrap: Wrap::Clamp,
+			mipmaps: false,
+			mipmap_filter: Filter::Linear,
+			anisotropy: None,
+			format: TextureFormat::Rgba8,
+		}
+	}
+}
+
+#[allow(dead_code)]
+impl TextureSettings {
+	/// Nearest filtering without mipmaps, for HUD sprites
+	pub fn pixel_art() -> Self {
+		Self {
+			min_filter: Filter::Nearest,
+			mag_filter: Filter::Nearest,
+			..Self::default()
+		}
+	}
+
+	/// Linear filtering with mipmaps, for textures seen from far away or at an angle
+	pub fn trilinear() -> Self {
+		Self {
+			wrap: Wrap::Repeat,
+			mipmaps: true,
+			..Self::default()
+		}
+	}
+
+	pub fn is_default(&self) -> bool {
+		*self == Self::default()
+	}
+
+	pub fn sampler(&self) -> Sampler {
+		let min_filter = match (self.mipmaps, self.min_filter, self.mipmap_filter) {
+			(false, Filter::Nearest, _) => MinFilter::Nearest,
+			(false, Filter::Linear, _) => MinFilter::Linear,
+			(true, Filter::Nearest, Filter::Nearest) => MinFilter::NearestMipmapNearest,
+			(true, Filter::Nearest, Filter::Linear) => MinFilter::NearestMipmapLinear,
+			(true, Filter::Linear, Filter::Nearest) => MinFilter::LinearMipmapNearest,
+			(true, Filter::Linear, Filter::Linear) => MinFilter::LinearMipmapLinear,
+		};
+		Sampler {
+			wrap_r: self.wrap.into(),
+			wrap_s: self.wrap.into(),
+			wrap_t: self.wrap.into(),
+			min_filter,
+			mag_filter: match self.mag_filter {
+				Filter::Nearest => MagFilter::Nearest,
+				Filter::Linear => MagFilter::Linear,
+			},
+			..Sampler::default()
+		}
+	}
+
+	/// Number of mipmaps below the base level for a texture of this size, 0 without mipmaps
+	pub fn mipmap_levels(&self, size: [u32; 2]) -> usize {
+		let max = size[0].max(size[1]).max(1);
+		if self.mipmaps {
+			(31 - max.leading_zeros()) as usize
+		} else {
+			0
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LoopMode {
+	Once,     // Stops at the last frame
+	Loop,     // Goes back to the first frame
+	PingPong, // Plays backwards after the last frame, then forwards again
+}
+
+impl Default for LoopMode {
+	fn default() -> Self {
+		Self::Loop
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+	pub state: String, // UV state
+	pub duration: f32, // Seconds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub event: Option<String>, // Sent when the frame starts
+}
+
+/// Sequence of UV states, played by `hud::Entity::play_animation`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation {
+	pub frames: Vec<Frame>,
+	#[serde(default)]
+	pub mode: LoopMode,
+}
+
+/// Border insets, in pixels, of the UV states of a panel texture.
+/// The corners keep their size, the edges stretch along one axis and the center along both
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NineSlice {
+	pub left: u32,
+	pub right: u32,
+	pub top: u32,
+	pub bottom: u32,
+}
+
+#[allow(dead_code)]
+impl NineSlice {
+	/// The 3x3 grid is made of 4x4 vertices, from the bottom left corner, row by row
+	pub const VERTICES: usize = 16;
+
+	/// Bounding box of a UV state: bottom left and top right corners
+	fn bounds(uv: &[(u32, u32)]) -> ((u32, u32), (u32, u32)) {
+		let min = (
+			uv.iter().map(|v| v.0).min().unwrap_or(0),
+			uv.iter().map(|v| v.1).min().unwrap_or(0),
+		);
+		let max = (
+			uv.iter().map(|v| v.0).max().unwrap_or(0),
+			uv.iter().map(|v| v.1).max().unwrap_or(0),
+		);
+		(min, max)
+	}
+
+	/// Whether the insets fit in a UV state
+	pub fn fits(&self, uv: &[(u32, u32)]) -> bool {
+		let (min, max) = Self::bounds(uv);
+		self.left + self.right <= max.0 - min.0 && self.top + self.bottom <= max.1 - min.1
+	}
+
+	/// UVs of the grid vertices for a UV state. UVs count from the bottom, so `bottom` is the first row
+	pub fn grid_uv(&self, uv: &[(u32, u32)]) -> Vec<(u32, u32)> {
+		let (min, max) = Self::bounds(uv);
+		let xs = [min.0, min.0 + self.left, max.0 - self.right, max.0];
+		let ys = [min.1, min.1 + self.bottom, max.1 - self.top, max.1];
+		ys.iter()
+			.flat_map(|&y| xs.iter().map(move |&x| (x, y)))
+			.collect()
+	}
+
+	/// Positions of the grid vertices for a panel of `size` pixels.
+	/// Panels smaller than the borders shrink them, so the corners don't overlap
+	pub fn grid_pos(&self, size: [u32; 2]) -> Vec<[i32; 2]> {
+		let shrink = |a: u32, b: u32, len: u32| {
+			if a + b > len {
+				let a = (a as f32 * len as f32 / (a + b) as f32) as u32;
+				(a, a)
+			} else {
+				(a, len - b)
+			}
+		};
+		let (left, right) = shrink(self.left, self.right, size[0]);
+		let (bottom, top) = shrink(self.bottom, self.top, size[1]);
+		let xs = [0, left, right, size[0]];
+		let ys = [0, bottom, top, size[1]];
+		ys.iter()
+			.flat_map(|&y| xs.iter().map(move |&x| [x as i32, y as i32]))
+			.collect()
+	}
+
+	/// Two triangles for each of the 9 quads, in the same order as a single quad
+	pub fn grid_indices() -> Vec<u8> {
+		let mut indices = Vec::new();
+		for row in 0..3 {
+			for col in 0..3 {
+				let bl = row * 4 + col;
+				let (br, tr, tl) = (bl + 1, bl + 5, bl + 4);
+				indices.extend_from_slice(&[bl, br, tr, bl, tr, tl]);
+			}
+		}
+		indices
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TextureData {
 	pub file: String,
 	pub default_uv: String,
-	pub uv: HashMap<String, Vec<(u32, u32)>>,
+	pub uv: BTreeMap<String, Vec<(u32, u32)>>, // Sorted, so generated files don't change order
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub animations: BTreeMap<String, Animation>,
+	#[serde(default, skip_serializing_if = "TextureSettings::is_default")]
+	pub settings: TextureSettings,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub nine_slice: Option<NineSlice>, // Used by `hud::Entity::panel`
 }
 
 impl TextureData {
-	pub fn load(file_loader: &mut FileLoader, file: &Path) -> Option<Self> {
+	pub fn load(file_loader: &mut FileLoader, file: &Path) -> Result<Self> {
 		let ron_path = TEXTURES_PATH.join(file);
 		println!("Opening {:?}", ron_path);
-		let f = file_loader
-			.load(ron_path)
-			.expect("Error opening RON Texture file. Path might be wrong?");
-
-		match from_reader(f) {
-			Ok(x) => Some(x),
-			Err(e) => {
-				eprintln!("Can't load texture data: {}", e);
-				None
+		let f = file_loader.load(&ron_path)?;
+
+		let tex_data: Self = from_reader(f).map_err(|e| {
+			// Errors that aren't syntax errors (like a missing field) don't have a position
+			let line = Some(e.position.line).filter(|l| *l > 0);
+			Error::parse(&ron_path, line, e.code.to_string())
+		})?;
+		if !tex_data.uv.contains_key(&tex_data.default_uv) {
+			return Err(Error::invalid(
+				&ron_path,
+				format!(
+					"default UV \"{}\" is not one of the UV states",
+					tex_data.default_uv
+				),
+			));
+		}
+		// Entities swap the UVs of their vertices when the state changes, so every state needs as many UVs as the default one
+		let uv_len = tex_data.uv[&tex_data.default_uv].len();
+		for (name, uv) in &tex_data.uv {
+			if uv.len() != uv_len {
+				return Err(Error::invalid(
+					&ron_path,
+					format!(
+						"UV state \"{}\" has {} UVs, but the default one has {}",
+						name,
+						uv.len(),
+						uv_len
+					),
+				));
+			}
+		}
+		if let Some(anisotropy) = tex_data.settings.anisotropy {
+			return Err(Error::invalid(
+				&ron_path,
+				format!(
+					"anisotropic filtering ({}x) isn't supported, remove `anisotropy`",
+					anisotropy
+				),
+			));
+		}
+		if let Some(nine_slice) = &tex_data.nine_slice {
+			for (name, uv) in &tex_data.uv {
+				if !nine_slice.fits(uv) {
+					return Err(Error::invalid(
+						&ron_path,
+						format!("the nine slice borders don't fit in UV state \"{}\"", name),
+					));
+				}
+			}
+		}
+		for (name, animation) in &tex_data.animations {
+			if animation.frames.is_empty() {
+				return Err(Error::invalid(
+					&ron_path,
+					format!("animation \"{}\" has no frames", name),
+				));
+			}
+			for frame in &animation.frames {
+				if !tex_data.uv.contains_key(&frame.state) {
+					return Err(Error::invalid(
+						&ron_path,
+						format!(
+							"animation \"{}\" uses \"{}\", which is not one of the UV states",
+							name, frame.state
+						),
+					));
+				}
+				if !(frame.duration > 0.0) {
+					return Err(Error::invalid(
+						&ron_path,
+						format!("animation \"{}\" has a frame without duration", name),
+					));
+				}
+			}
+		}
+		Ok(tex_data)
+	}
+
+	/// Checks that the UVs are inside the image, which `load` can't do without reading it.
+	/// UVs outside of it don't fail at runtime, they only show the wrapped or clamped texture
+	pub fn check_uvs(&self, file: &Path, size: (u32, u32)) -> Result<()> {
+		for (name, uv) in &self.uv {
+			if let Some((x, y)) = uv.iter().find(|(x, y)| *x > size.0 || *y > size.1) {
+				return Err(Error::invalid(
+					TEXTURES_PATH.join(file),
+					format!(
+						"UV ({}, {}) of state \"{}\" is outside the {}x{} image {:?}",
+						x, y, name, size.0, size.1, self.file
+					),
+				));
 			}
 		}
+		Ok(())
 	}
 }
@@ -1,46 +1,251 @@
 use luminance::context::GraphicsContext;
-use luminance::pixel::{Depth32F, NormRGBA8UI};
+use luminance::pixel::{Depth32F, NormRGBA8UI, Pixel};
 use luminance::texture::{Dim2, GenMipmaps, Sampler, Texture};
 
 use image;
-
-use zip::ZipArchive;
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, Luma, LumaA, Rgb, Rgba};
 
 use lazy_static::lazy_static;
 
 use cgmath::Rad;
 
-use std::fs::File;
-use std::io::{Cursor, Read, Seek};
+use std::collections::BTreeSet;
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
+use super::cook::{self, CookedImage};
+use super::error::{Error, Result};
+use super::pack::{glob_match, IntegrityError};
+use super::texture::{AnyTexture, TextureFormat, TextureSettings};
+use super::vfs::{normalize, Layer, Metadata};
+use super::watcher::Watcher;
+
 // read the texture into memory as a whole bloc (i.e. no streaming)
-pub fn read_image(file_loader: &mut FileLoader, path: &Path) -> Option<image::RgbaImage> {
-	image::load(
-		file_loader.load(path).expect("Error loading file"),
-		image::ImageFormat::from_path(path).expect("Error loading format"),
-	)
-	.map(|img| img.flipv().to_rgba())
-	.ok()
+pub fn read_image(file_loader: &mut FileLoader, path: &Path) -> Result<image::RgbaImage> {
+	if let Some(cooked_path) = file_loader.find_cooked(path, cook::IMAGE_EXT) {
+		let data = file_loader.load(&cooked_path)?.into_inner();
+		let img: CookedImage = cook::decode(&data).map_err(|e| Error::decode(&cooked_path, e))?;
+		return image::RgbaImage::from_raw(img.width, img.height, img.texels)
+			.ok_or_else(|| Error::decode(&cooked_path, "wrong number of texels"));
+	}
+	decode_image(file_loader.load(path)?, path)
+}
+
+// decode an image already in memory, so it can be done outside the main thread
+pub fn decode_image(data: Cursor<Vec<u8>>, path: &Path) -> Result<image::RgbaImage> {
+	decode_dynamic_image(data, path).map(|img| img.to_rgba())
+}
+
+// same as `decode_image`, but keeping the pixel format of the file
+fn decode_dynamic_image(data: Cursor<Vec<u8>>, path: &Path) -> Result<DynamicImage> {
+	let format = image::ImageFormat::from_path(path).map_err(|e| Error::decode(path, e))?;
+	image::load(data, format)
+		.map(|img| img.flipv())
+		.map_err(|e| Error::decode(path, e))
+}
+
+// decoding an .hdr as a `DynamicImage` maps it to 8 bits, this keeps the floats
+fn decode_hdr(data: Cursor<Vec<u8>>, path: &Path) -> Result<TextureImage> {
+	let decoder = image::hdr::HdrDecoder::new(data).map_err(|e| Error::decode(path, e))?;
+	let (width, height) = (decoder.metadata().width, decoder.metadata().height);
+	let texels: Vec<f32> = decoder
+		.read_image_hdr()
+		.map_err(|e| Error::decode(path, e))?
+		.into_iter()
+		.flat_map(|p| p.0.to_vec())
+		.collect();
+	let img = ImageBuffer::from_raw(width, height, texels)
+		.ok_or_else(|| Error::decode(path, "wrong number of texels"))?;
+	Ok(TextureImage::RgbF32(image::imageops::flip_vertical(&img)))
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+	path.extension()
+		.map(|e| e.to_string_lossy().eq_ignore_ascii_case(ext))
+		.unwrap_or(false)
+}
+
+/// Texels in the pixel format of the texture they'll be uploaded to, flipped like `read_image` does
+pub enum TextureImage {
+	Rgba8(image::RgbaImage),
+	Srgba8(image::RgbaImage),
+	R8(image::GrayImage),
+	R16(ImageBuffer<Luma<u16>, Vec<u16>>),
+	Rgba16(ImageBuffer<Rgba<u16>, Vec<u16>>),
+	RgbF32(ImageBuffer<Rgb<f32>, Vec<f32>>),
+}
+
+impl TextureImage {
+	/// Converts the image to a format, expanding or reducing the channels and their depth as needed
+	pub fn new(img: DynamicImage, format: TextureFormat) -> Self {
+		let (width, height) = (img.width(), img.height());
+		match format {
+			TextureFormat::Rgba8 => Self::Rgba8(img.to_rgba()),
+			TextureFormat::Srgba8 => Self::Srgba8(img.to_rgba()),
+			TextureFormat::R8 => Self::R8(img.to_luma()),
+			TextureFormat::R16 => Self::R16(match &img {
+				DynamicImage::ImageLuma16(img) => img.clone(),
+				DynamicImage::ImageLumaA16(img) => image::imageops::grayscale(img),
+				DynamicImage::ImageRgb16(img) => image::imageops::grayscale(img),
+				DynamicImage::ImageRgba16(img) => image::imageops::grayscale(img),
+				_ => {
+					let gray = img.to_luma();
+					ImageBuffer::from_fn(width, height, |x, y| {
+						Luma([gray.get_pixel(x, y).0[0] as u16 * 257])
+					})
+				}
+			}),
+			TextureFormat::Rgba16 => Self::Rgba16(match &img {
+				DynamicImage::ImageRgba16(img) => img.clone(),
+				DynamicImage::ImageRgb16(img) => ImageBuffer::from_fn(width, height, |x, y| {
+					let Rgb([r, g, b]) = *img.get_pixel(x, y);
+					Rgba([r, g, b, u16::MAX])
+				}),
+				DynamicImage::ImageLumaA16(img) => ImageBuffer::from_fn(width, height, |x, y| {
+					let LumaA([l, a]) = *img.get_pixel(x, y);
+					Rgba([l, l, l, a])
+				}),
+				DynamicImage::ImageLuma16(img) => ImageBuffer::from_fn(width, height, |x, y| {
+					let Luma([l]) = *img.get_pixel(x, y);
+					Rgba([l, l, l, u16::MAX])
+				}),
+				_ => {
+					let rgba = img.to_rgba();
+					ImageBuffer::from_fn(width, height, |x, y| {
+						let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+						Rgba([
+							r as u16 * 257,
+							g as u16 * 257,
+							b as u16 * 257,
+							a as u16 * 257,
+						])
+					})
+				}
+			}),
+			TextureFormat::RgbF32 => {
+				let rgb = img.to_rgb();
+				Self::RgbF32(ImageBuffer::from_fn(width, height, |x, y| {
+					let Rgb([r, g, b]) = *rgb.get_pixel(x, y);
+					Rgb([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0])
+				}))
+			}
+			TextureFormat::Source => {
+				let format = match img.color() {
+					ColorType::L8 => TextureFormat::R8,
+					ColorType::L16 => TextureFormat::R16,
+					ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => TextureFormat::Rgba16,
+					_ => TextureFormat::Rgba8,
+				};
+				Self::new(img, format)
+			}
+		}
+	}
+}
+
+/// Reads an image in the pixel format of the texture it's going to be uploaded to.
+/// Cooked images are 8-bit RGBA, only 16-bit and HDR images are always read from the source file
+pub fn read_texture_image(
+	file_loader: &mut FileLoader,
+	path: &Path,
+	format: TextureFormat,
+) -> Result<TextureImage> {
+	if file_loader.find_cooked(path, cook::IMAGE_EXT).is_some() {
+		let img = DynamicImage::ImageRgba8(read_image(file_loader, path)?);
+		return Ok(TextureImage::new(img, format));
+	}
+	let data = file_loader.load(path)?;
+	match format {
+		TextureFormat::RgbF32 | TextureFormat::Source if has_extension(path, "hdr") => {
+			decode_hdr(data, path)
+		}
+		_ => Ok(TextureImage::new(decode_dynamic_image(data, path)?, format)),
+	}
 }
 
 pub fn load_from_disk<C: GraphicsContext>(
 	surface: &mut C,
 	img: image::RgbaImage,
-) -> Texture<Dim2, NormRGBA8UI> {
-	let (width, height) = img.dimensions();
-	let texels = img.into_raw();
+) -> Result<Texture<Dim2, NormRGBA8UI>> {
+	load_with_settings(surface, img, &TextureSettings::default())
+}
 
-	// create the luminance texture; the third argument is the number of mipmaps we want (leave it
-	// to 0 for now) and the latest is the sampler to use when sampling the texels in the
-	// shader (we’ll just use the default one)
-	let tex = Texture::new(surface, [width, height], 0, Sampler::default())
-		.expect("luminance texture creation error");
+pub fn load_with_settings<C: GraphicsContext>(
+	surface: &mut C,
+	img: image::RgbaImage,
+	settings: &TextureSettings,
+) -> Result<Texture<Dim2, NormRGBA8UI>> {
+	let size = img.dimensions();
+	upload_texels(surface, size, &img.into_raw(), settings)
+}
 
-	// the first argument disables mipmap generation (we don’t care so far)
-	tex.upload_raw(GenMipmaps::No, &texels).unwrap();
+/// Uploads an image to a texture of its pixel format
+pub fn load_texture<C: GraphicsContext>(
+	surface: &mut C,
+	img: TextureImage,
+	settings: &TextureSettings,
+) -> Result<AnyTexture> {
+	Ok(match img {
+		TextureImage::Rgba8(img) => AnyTexture::Rgba8(load_with_settings(surface, img, settings)?),
+		TextureImage::Srgba8(img) => {
+			let size = img.dimensions();
+			AnyTexture::Srgba8(upload_texels(surface, size, &img.into_raw(), settings)?)
+		}
+		TextureImage::R8(img) => {
+			let size = img.dimensions();
+			AnyTexture::R8(upload_texels(surface, size, &img.into_raw(), settings)?)
+		}
+		TextureImage::R16(img) => {
+			let size = img.dimensions();
+			AnyTexture::R16(upload_texels(surface, size, &img.into_raw(), settings)?)
+		}
+		TextureImage::Rgba16(img) => {
+			let size = img.dimensions();
+			AnyTexture::Rgba16(upload_texels(surface, size, &img.into_raw(), settings)?)
+		}
+		TextureImage::RgbF32(img) => {
+			let size = img.dimensions();
+			AnyTexture::RgbF32(upload_texels(surface, size, &img.into_raw(), settings)?)
+		}
+	})
+}
 
-	tex
+fn upload_texels<C: GraphicsContext, P: Pixel>(
+	surface: &mut C,
+	(width, height): (u32, u32),
+	texels: &[P::RawEncoding],
+	settings: &TextureSettings,
+) -> Result<Texture<Dim2, P>> {
+	// create the luminance texture; the third argument is the number of mipmaps below the base
+	// level and the latest is the sampler to use when sampling the texels in the shader
+	let tex = Texture::new(
+		surface,
+		[width, height],
+		settings.mipmap_levels([width, height]),
+		settings.sampler(),
+	)
+	.map_err(|e| Error::upload(None, e))?;
+
+	// the mipmaps are generated from the base level
+	let gen_mipmaps = if settings.mipmaps {
+		GenMipmaps::Yes
+	} else {
+		GenMipmaps::No
+	};
+	tex.upload_raw(gen_mipmaps, texels)
+		.map_err(|e| Error::upload(None, e))?;
+
+	Ok(tex)
+}
+
+/// Magenta & black checkerboard, to use instead of a texture that can't be loaded
+pub fn placeholder_image() -> image::RgbaImage {
+	image::RgbaImage::from_fn(64, 64, |x, y| {
+		if (x / 8 + y / 8) % 2 == 0 {
+			image::Rgba([255, 0, 255, 255])
+		} else {
+			image::Rgba([0, 0, 0, 255])
+		}
+	})
 }
 
 #[derive(Clone, Copy)]
@@ -60,6 +265,17 @@ impl RgbaColor {
 	pub fn array(&self) -> [u8; 4] {
 		[self.r, self.g, self.b, self.a]
 	}
+
+	/// Channels from 0 to 1, like shaders use them
+	#[allow(dead_code)]
+	pub fn float_array(&self) -> [f32; 4] {
+		[
+			self.r as f32 / 255.0,
+			self.g as f32 / 255.0,
+			self.b as f32 / 255.0,
+			self.a as f32 / 255.0,
+		]
+	}
 }
 
 impl std::fmt::Debug for RgbaColor {
@@ -79,62 +295,232 @@ static ZIP: Option<&[u8]> = Some(include_bytes!("../../assets.zip"));
 #[cfg(not(any(feature = "pack")))]
 static ZIP: Option<&[u8]> = None;
 
+// Priority of the packs opened at runtime, over the base assets
+const PACK_PRIORITY: i32 = 10;
+
 lazy_static! {
 	pub static ref ASSETS_PATH: &'static Path = Path::new("assets");
 }
 
+/// Virtual filesystem made of several layers (directories, zip packs, memory buffers).
+/// Files are looked up in the layers from the highest priority to the lowest, so a mod or patch can override the base assets
 pub struct FileLoader<'a> {
-	ar: Option<ZipArchive<Cursor<&'a [u8]>>>,
+	layers: Vec<Layer<'a>>, // Sorted by priority, highest first
+	watcher: Watcher,
+	listing: Option<Vec<String>>, // Files of every layer, cached until a layer is mounted or unmounted
 }
 
+#[allow(dead_code)]
 impl<'a> FileLoader<'a> {
+	/// Creates a loader with the base assets mounted: the packed zip with the `pack` feature or the `assets/` directory without it
 	pub fn new() -> Self {
+		let mut loader = Self::empty();
 		if cfg!(feature = "pack") {
-			let buf_reader = Cursor::new(ZIP.unwrap());
-			let ar = ZipArchive::new(buf_reader).unwrap();
-			return Self { ar: Some(ar) };
+			loader.mount(
+				Layer::zip("base", 0, ZIP.unwrap(), *ASSETS_PATH).expect("Embedded pack is broken"),
+			);
 		} else {
-			return Self {
-				ar: if let None = ZIP {
-					None
-				} else {
-					unreachable!("ERROR, LOADED ZIP, NOT")
-				},
-			};
+			if ZIP.is_some() {
+				unreachable!("ERROR, LOADED ZIP, NOT")
+			}
+			loader.mount(Layer::directory("base", 0, *ASSETS_PATH));
 		}
+		loader
 	}
 
-	pub fn load<P: AsRef<Path>>(&mut self, p: P) -> Option<Cursor<Vec<u8>>> {
-		println!(
-			"Loading {}",
-			&Self::path_buf_as_zip_path(ASSETS_PATH.join(&p))
-		);
-		if cfg!(feature = "pack") {
-			let ar = self.ar.as_mut().unwrap();
-			if let Ok(mut f) = ar.by_name(&Self::path_buf_as_zip_path(ASSETS_PATH.join(p))) {
-				let mut buf = Vec::new();
-				f.read_to_end(&mut buf).expect("Error loading file");
-				Some(Cursor::new(buf))
-			} else {
-				None
+	/// Creates a loader with the zip pack at `path` mounted over the base assets.
+	/// If the pack can't be opened the error is reported and only the base assets are used.
+	/// Its files are checked when they're loaded, the `validate` tool checks the whole pack
+	pub fn from_pack<P: AsRef<Path>>(path: P) -> Self {
+		let mut loader = Self::new();
+		match Layer::zip_file("pack", PACK_PRIORITY, &path, *ASSETS_PATH) {
+			Ok(layer) => loader.mount(layer),
+			Err(e) => eprintln!(
+				"Can't open asset pack {:?}, using the base assets: {}",
+				path.as_ref(),
+				e
+			),
+		}
+		loader
+	}
+
+	/// Creates a loader with only the zip pack at `path` mounted, to check a pack on its own
+	pub fn pack_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let mut loader = Self::empty();
+		loader.mount(Layer::zip_file("pack", PACK_PRIORITY, path, *ASSETS_PATH)?);
+		Ok(loader)
+	}
+
+	/// Creates a loader without any layer
+	pub fn empty() -> Self {
+		Self {
+			layers: Vec::new(),
+			watcher: Watcher::new(),
+			listing: None,
+		}
+	}
+
+	/// Adds a layer. If there are other layers with the same priority the new one is searched before them
+	pub fn mount(&mut self, layer: Layer<'a>) {
+		let i = self
+			.layers
+			.iter()
+			.position(|l| l.priority() <= layer.priority())
+			.unwrap_or(self.layers.len());
+		self.layers.insert(i, layer);
+		self.listing = None;
+	}
+
+	pub fn unmount<T: ToString>(&mut self, name: T) -> Option<Layer<'a>> {
+		let name = name.to_string();
+		let i = self.layers.iter().position(|l| l.name() == name)?;
+		self.listing = None;
+		Some(self.layers.remove(i))
+	}
+
+	/// The mounted layers, in lookup order
+	pub fn layers(&self) -> &[Layer<'a>] {
+		&self.layers
+	}
+
+	pub fn load<P: AsRef<Path>>(&mut self, p: P) -> Result<Cursor<Vec<u8>>> {
+		self.load_with_layer(p).map(|(f, _)| f)
+	}
+
+	/// Loads a file, also returning the name of the layer that served it
+	pub fn load_with_layer<P: AsRef<Path>>(&mut self, p: P) -> Result<(Cursor<Vec<u8>>, String)> {
+		let p = p.as_ref();
+		let mut error = None;
+		for layer in self.layers.iter_mut() {
+			match layer.load(p) {
+				Ok(Some(buf)) => {
+					// The error of a higher layer is only reported here, otherwise the caller gets it
+					if let Some(e) = error {
+						eprintln!("{}, using the file in {}", e, layer.name());
+					}
+					if let Some(disk_path) = layer.disk_path(p) {
+						self.watcher.watch(p, disk_path);
+					}
+					return Ok((Cursor::new(buf), layer.name().to_string()));
+				}
+				Ok(None) => (),
+				// A corrupt file is skipped, so a lower layer can still serve it
+				Err(e) => error = error.or(Some(e)),
+			}
+		}
+		// If the file was found but it was corrupt, that's more useful than not found
+		Err(error.unwrap_or_else(|| Error::NotFound(p.to_path_buf())))
+	}
+
+	/// Name of the layer that would serve a file, `None` if no layer has it
+	pub fn origin<P: AsRef<Path>>(&mut self, p: P) -> Option<&str> {
+		let p = p.as_ref();
+		for layer in self.layers.iter_mut() {
+			if layer.contains(p) {
+				return Some(layer.name());
+			}
+		}
+		None
+	}
+
+	/// Path of the cooked form of a file (see `cook`), if the layer that would serve the file has it.
+	/// A layer with only the source file overrides the cooked files of the layers below it
+	pub fn find_cooked<P: AsRef<Path>>(&mut self, p: P, ext: &str) -> Option<PathBuf> {
+		let p = p.as_ref();
+		let cooked = PathBuf::from(cook::cooked_path(&normalize(p), ext));
+		for layer in self.layers.iter_mut() {
+			if layer.contains(&cooked) {
+				return Some(cooked);
+			} else if layer.contains(p) {
+				return None;
 			}
+		}
+		None
+	}
+
+	/// Whether any layer has the file
+	pub fn exists<P: AsRef<Path>>(&mut self, p: P) -> bool {
+		self.layers.iter_mut().any(|l| l.contains(&p))
+	}
+
+	/// Metadata of the file in the layer that would serve it
+	pub fn metadata<P: AsRef<Path>>(&mut self, p: P) -> Option<Metadata> {
+		self.layers.iter_mut().find_map(|l| l.metadata(&p))
+	}
+
+	/// Every file in every layer, sorted and without duplicates
+	pub fn files(&mut self) -> Vec<PathBuf> {
+		self.listing().iter().map(PathBuf::from).collect()
+	}
+
+	// The files of every layer, listed again only when the layers changed
+	fn listing(&mut self) -> &[String] {
+		if self.listing.is_none() {
+			let mut files: BTreeSet<String> = BTreeSet::new();
+			for layer in self.layers.iter_mut() {
+				files.extend(layer.files().iter().cloned());
+			}
+			self.listing = Some(files.into_iter().collect());
+		}
+		self.listing.as_ref().unwrap()
+	}
+
+	/// Lists the files of every layer again, after files were added to or removed from a directory layer
+	pub fn rescan(&mut self) {
+		for layer in self.layers.iter_mut() {
+			layer.rescan();
+		}
+		self.listing = None;
+	}
+
+	/// Files matching a glob pattern, see `pack::glob_match`. E.g. `models/*.obj` or `**/*.ron`
+	pub fn glob(&mut self, pattern: &str) -> Vec<PathBuf> {
+		self.listing()
+			.iter()
+			.filter(|p| glob_match(pattern, p))
+			.map(PathBuf::from)
+			.collect()
+	}
+
+	/// Files and directories directly inside `dir` (`""` for the root), sorted.
+	/// Directories only exist if there's a file inside them
+	pub fn list_dir<P: AsRef<Path>>(&mut self, dir: P) -> Vec<PathBuf> {
+		let dir = normalize(dir);
+		let prefix = if dir.is_empty() {
+			dir.clone()
 		} else {
-			if let Ok(mut f) = File::open(ASSETS_PATH.join(p)) {
-				let mut buf = Vec::new();
-				f.read_to_end(&mut buf).expect("Error loading file");
-				Some(Cursor::new(buf))
-			} else {
-				None
+			format!("{}/", dir)
+		};
+		let mut entries = BTreeSet::new();
+		for file in self.listing() {
+			if file.starts_with(&prefix) {
+				let name = file[prefix.len()..].split('/').next().unwrap();
+				entries.insert(Path::new(&dir).join(name));
 			}
 		}
+		entries.into_iter().collect()
+	}
+
+	pub fn is_dir<P: AsRef<Path>>(&mut self, p: P) -> bool {
+		let prefix = format!("{}/", normalize(p));
+		self.listing().iter().any(|f| f.starts_with(&prefix))
 	}
 
-	fn path_buf_as_zip_path(p: PathBuf) -> String {
-		let mut r = Vec::<String>::new();
-		for s in p.into_iter() {
-			r.push(s.to_str().unwrap().to_string());
+	/// Checks the files of every mounted pack against their manifests
+	pub fn verify(&mut self) -> Vec<(String, IntegrityError)> {
+		let mut errors = Vec::new();
+		for layer in self.layers.iter_mut() {
+			for e in layer.verify() {
+				errors.push((layer.name().to_string(), e));
+			}
 		}
-		r.join("/")
+		errors
+	}
+
+	/// Paths (relative to the assets root) of the loaded files that changed on disk since the last call.
+	/// Only files served by directory layers can change
+	pub fn changed_files(&mut self) -> Vec<PathBuf> {
+		self.watcher.poll()
 	}
 }
 
@@ -0,0 +1,98 @@
+// Runs the checks of every loader over the assets, without a GPU or an audio device
+
+use ambisonic::rodio::buffer::SamplesBuffer;
+
+use rusttype::Font as RTFont;
+
+use wavefront_obj::mtl;
+
+use std::collections::BTreeSet;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+
+use super::assets::Asset;
+use super::cook::{self, CookedFont};
+use super::error::{Error, Result};
+use super::pack::AssetKind;
+use super::spatial::obj::Obj;
+use super::texture::TextureData;
+use super::{FileLoader, MODELS_PATH, TEXTURES_PATH};
+
+/// What `validate` found
+#[derive(Debug)]
+pub struct Report {
+	pub checked: usize,     // Assets that were loaded
+	pub errors: Vec<Error>, // Sorted by file
+}
+
+impl Report {
+	pub fn is_ok(&self) -> bool {
+		self.errors.is_empty()
+	}
+}
+
+/// Loads every asset of the `FileLoader` like the engine would, and checks what the loaders can't
+/// (like UVs outside of their image). Cooked files are checked through the asset they were cooked from
+pub fn validate(file_loader: &mut FileLoader) -> Report {
+	let mut errors = file_loader
+		.verify()
+		.into_iter()
+		.map(|(layer, e)| Error::Corrupt(layer, e))
+		.collect::<Vec<_>>();
+
+	// A cooked image or mesh replaces its source in packs, so both are checked as the source
+	let mut assets = BTreeSet::new();
+	for file in file_loader.files() {
+		let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+		if ext == cook::MESH_EXT || ext == cook::IMAGE_EXT {
+			assets.insert(file.with_extension(""));
+		} else {
+			assets.insert(file);
+		}
+	}
+
+	let mut checked = 0;
+	for path in &assets {
+		let res = match AssetKind::from_path(path) {
+			AssetKind::Image => image::RgbaImage::load(file_loader, path).map(|_| ()),
+			AssetKind::TextureData => match path.strip_prefix(&*TEXTURES_PATH) {
+				Ok(file) => check_texture_data(file_loader, file),
+				Err(_) => continue, // Not a TextureData, there's nothing else in RON yet
+			},
+			AssetKind::Model => Obj::load(
+				file_loader,
+				path.strip_prefix(&*MODELS_PATH).unwrap_or(path),
+			)
+			.map(|_| ()),
+			AssetKind::Material => check_mtl(file_loader, path),
+			AssetKind::Font => RTFont::load(file_loader, path).map(|_| ()),
+			AssetKind::Sound => SamplesBuffer::<f32>::load(file_loader, path).map(|_| ()),
+			AssetKind::Cooked => CookedFont::load(file_loader, path).map(|_| ()), // Only glyphs are left
+			AssetKind::Other => continue,
+		};
+		checked += 1;
+		if let Err(e) = res {
+			errors.push(e);
+		}
+	}
+	errors.sort_by_key(|e| e.path().map(PathBuf::from));
+	Report { checked, errors }
+}
+
+fn check_texture_data(file_loader: &mut FileLoader, file: &Path) -> Result<()> {
+	let tex_data = TextureData::load(file_loader, file)?;
+	let img = image::RgbaImage::load(file_loader, &TEXTURES_PATH.join(&tex_data.file))?;
+	tex_data.check_uvs(file, img.dimensions())
+}
+
+// Material libraries are parsed with their OBJ, this catches the ones no OBJ uses (yet)
+fn check_mtl(file_loader: &mut FileLoader, path: &Path) -> Result<()> {
+	let mut content = String::new();
+	file_loader
+		.load(path)?
+		.read_to_string(&mut content)
+		.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+	mtl::parse(content)
+		.map(|_| ())
+		.map_err(|e| Error::parse(path, Some(e.line_number), e.message))
+}
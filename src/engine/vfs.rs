@@ -0,0 +1,371 @@
+use zip::ZipArchive;
+
+use ron::de::from_bytes;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::error::{Error, Result};
+use super::pack::{AssetKind, IntegrityError, Manifest, MANIFEST_FILE};
+
+/// Turns a path into the `/` separated form used as the key of every layer
+pub fn normalize<P: AsRef<Path>>(p: P) -> String {
+	let mut r = Vec::<String>::new();
+	for s in p.as_ref().iter() {
+		r.push(s.to_str().unwrap().to_string());
+	}
+	r.join("/")
+}
+
+/// Information about a file, the same for every kind of layer
+#[derive(Debug, Clone)]
+pub struct Metadata {
+	pub size: u64, // Uncompressed
+	pub kind: AssetKind,
+	pub layer: String,                // Name of the layer with the file
+	pub modified: Option<SystemTime>, // Only known for directory layers
+}
+
+pub enum LayerSource<'a> {
+	/// Loose files inside a directory
+	Directory(PathBuf),
+	/// A zip archive, with the files stored under a root dir inside it (`assets` for the packs built by `build.rs`)
+	Zip {
+		archive: ZipArchive<Cursor<Cow<'a, [u8]>>>,
+		root: String,
+		manifest: Option<Manifest>,
+	},
+	/// Files kept in memory, useful for generated assets and tests
+	Memory(HashMap<String, Vec<u8>>),
+}
+
+/// A source of files mounted in a `FileLoader`. Layers with a higher priority are searched first
+pub struct Layer<'a> {
+	name: String,
+	priority: i32,
+	source: LayerSource<'a>,
+	listing: Option<Vec<String>>, // Cached `files`, sorted
+}
+
+#[allow(dead_code)]
+impl<'a> Layer<'a> {
+	pub fn directory<T: ToString, P: AsRef<Path>>(name: T, priority: i32, path: P) -> Self {
+		Self {
+			name: name.to_string(),
+			priority,
+			source: LayerSource::Directory(path.as_ref().to_path_buf()),
+			listing: None,
+		}
+	}
+
+	pub fn zip<T: ToString, D: Into<Cow<'a, [u8]>>, P: AsRef<Path>>(
+		name: T,
+		priority: i32,
+		data: D,
+		root: P,
+	) -> Result<Self> {
+		let name = name.to_string();
+		let mut archive =
+			ZipArchive::new(Cursor::new(data.into())).map_err(|e| Error::decode(&name, e))?;
+		let root = normalize(root);
+		let manifest: Option<Manifest> = match read_zip_file(&mut archive, &root, MANIFEST_FILE)
+			.map_err(|e| Error::Io(PathBuf::from(zip_name(&root, MANIFEST_FILE)), e))?
+		{
+			Some(buf) => match from_bytes(&buf) {
+				Ok(manifest) => Some(manifest),
+				Err(e) => {
+					eprintln!(
+						"Can't read the manifest of {}, it won't be verified: {}",
+						name, e
+					);
+					None
+				}
+			},
+			None => None,
+		};
+		Ok(Self {
+			name,
+			priority,
+			source: LayerSource::Zip {
+				archive,
+				root,
+				manifest,
+			},
+			listing: None,
+		})
+	}
+
+	/// Opens a zip pack stored on disk, reading it whole into memory
+	pub fn zip_file<T: ToString, P: AsRef<Path>, R: AsRef<Path>>(
+		name: T,
+		priority: i32,
+		path: P,
+		root: R,
+	) -> Result<Layer<'static>> {
+		let path = path.as_ref();
+		let mut buf = Vec::new();
+		File::open(path)
+			.and_then(|mut f| f.read_to_end(&mut buf))
+			.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+		Layer::zip(name, priority, buf, root).map_err(|e| match e {
+			// The name of the layer isn't useful when opening a file
+			Error::Decode(_, msg) => Error::Decode(path.to_path_buf(), msg),
+			e => e,
+		})
+	}
+
+	pub fn memory<T: ToString>(name: T, priority: i32) -> Self {
+		Self {
+			name: name.to_string(),
+			priority,
+			source: LayerSource::Memory(HashMap::new()),
+			listing: None,
+		}
+	}
+
+	/// Adds a file to a memory layer, returns false for the other kinds of layers
+	pub fn insert<P: AsRef<Path>>(&mut self, p: P, data: Vec<u8>) -> bool {
+		if let LayerSource::Memory(files) = &mut self.source {
+			files.insert(normalize(p), data);
+			self.listing = None;
+			true
+		} else {
+			false
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn priority(&self) -> i32 {
+		self.priority
+	}
+
+	pub fn source(&self) -> &LayerSource<'a> {
+		&self.source
+	}
+
+	/// Where the file lives on disk, only for directory layers
+	pub fn disk_path<P: AsRef<Path>>(&self, p: P) -> Option<PathBuf> {
+		if let LayerSource::Directory(dir) = &self.source {
+			Some(dir.join(p))
+		} else {
+			None
+		}
+	}
+
+	/// Loads a file from this layer, `None` if it isn't here.
+	/// Files from packs with a manifest are verified, returning an error if they don't match it
+	pub fn load<P: AsRef<Path>>(&mut self, p: P) -> Result<Option<Vec<u8>>> {
+		let layer_name = &self.name;
+		match &mut self.source {
+			LayerSource::Directory(dir) => {
+				let disk_path = dir.join(p);
+				if let Ok(mut f) = File::open(&disk_path) {
+					let mut buf = Vec::new();
+					f.read_to_end(&mut buf)
+						.map_err(|e| Error::Io(disk_path, e))?;
+					Ok(Some(buf))
+				} else {
+					Ok(None)
+				}
+			}
+			LayerSource::Zip {
+				archive,
+				root,
+				manifest,
+			} => {
+				let path = normalize(p);
+				let buf = read_zip_file(archive, root, &path)
+					.map_err(|e| Error::Io(PathBuf::from(zip_name(root, &path)), e))?;
+				match buf {
+					Some(buf) => {
+						if let Some(manifest) = manifest {
+							manifest
+								.verify(&path, &buf)
+								.map_err(|e| Error::Corrupt(layer_name.clone(), e))?;
+						}
+						Ok(Some(buf))
+					}
+					None => Ok(None),
+				}
+			}
+			LayerSource::Memory(files) => Ok(files.get(&normalize(p)).cloned()),
+		}
+	}
+
+	/// Whether the file is in this layer
+	pub fn contains<P: AsRef<Path>>(&mut self, p: P) -> bool {
+		match &mut self.source {
+			LayerSource::Directory(dir) => dir.join(p).is_file(),
+			LayerSource::Zip { archive, root, .. } => {
+				let path = normalize(p);
+				path != MANIFEST_FILE && archive.by_name(&zip_name(root, &path)).is_ok()
+			}
+			LayerSource::Memory(files) => files.contains_key(&normalize(p)),
+		}
+	}
+
+	pub fn metadata<P: AsRef<Path>>(&mut self, p: P) -> Option<Metadata> {
+		let path = normalize(&p);
+		let (size, modified) = match &mut self.source {
+			LayerSource::Directory(dir) => {
+				let m = fs::metadata(dir.join(p)).ok().filter(|m| m.is_file())?;
+				(m.len(), m.modified().ok())
+			}
+			LayerSource::Zip { archive, root, .. } => {
+				if path == MANIFEST_FILE {
+					return None;
+				}
+				(archive.by_name(&zip_name(root, &path)).ok()?.size(), None)
+			}
+			LayerSource::Memory(files) => (files.get(&path)?.len() as u64, None),
+		};
+		Some(Metadata {
+			size,
+			kind: AssetKind::from_path(&path),
+			layer: self.name.clone(),
+			modified,
+		})
+	}
+
+	/// Every file in this layer, `/` separated, relative to its root and sorted.
+	/// It's listed the first time and cached, see `rescan`
+	pub fn files(&mut self) -> &[String] {
+		if self.listing.is_none() {
+			let mut files = self.scan();
+			files.sort();
+			self.listing = Some(files);
+		}
+		self.listing.as_ref().unwrap()
+	}
+
+	/// Lists the files again next time, for directory layers whose files were added or removed on disk
+	pub fn rescan(&mut self) {
+		self.listing = None
+	}
+
+	fn scan(&mut self) -> Vec<String> {
+		let mut files = Vec::new();
+		match &mut self.source {
+			LayerSource::Directory(dir) => list_dir_recursive(dir, "", &mut files),
+			LayerSource::Zip { archive, root, .. } => {
+				let prefix = zip_prefix(root);
+				for i in 0..archive.len() {
+					if let Ok(f) = archive.by_index(i) {
+						if f.name().starts_with(&prefix) && !f.name().ends_with('/') {
+							let path = f.name()[prefix.len()..].to_string();
+							if path != MANIFEST_FILE {
+								files.push(path);
+							}
+						}
+					}
+				}
+			}
+			LayerSource::Memory(memory_files) => files.extend(memory_files.keys().cloned()),
+		}
+		files
+	}
+
+	/// Checks every file of a pack against its manifest. Layers without a manifest are always valid
+	pub fn verify(&mut self) -> Vec<IntegrityError> {
+		let mut errors = Vec::new();
+		if let LayerSource::Zip {
+			archive,
+			root,
+			manifest: Some(manifest),
+		} = &mut self.source
+		{
+			for (path, _) in manifest.entries.iter() {
+				match read_zip_file(archive, root, path) {
+					Ok(Some(buf)) => {
+						if let Err(e) = manifest.verify(path, &buf) {
+							errors.push(e)
+						}
+					}
+					Ok(None) => errors.push(IntegrityError::Missing(path.clone())),
+					Err(e) => errors.push(IntegrityError::Unreadable(path.clone(), e)),
+				}
+			}
+			// Like `scan`, the entries outside of the root aren't part of the layer
+			let prefix = zip_prefix(root);
+			for i in 0..archive.len() {
+				if let Ok(f) = archive.by_index(i) {
+					let path = match f.name().strip_prefix(&prefix) {
+						Some(path) if !path.ends_with('/') => path.to_string(),
+						_ => continue,
+					};
+					if path != MANIFEST_FILE && manifest.get(&path).is_none() {
+						errors.push(IntegrityError::NotInManifest(path));
+					}
+				}
+			}
+		}
+		errors
+	}
+}
+
+// Name inside the zip of a file, with `path` relative to `root`
+fn zip_name(root: &str, path: &str) -> String {
+	normalize(Path::new(root).join(path))
+}
+
+// Start of the names inside the zip of the files under `root`, nothing if the files are at the top
+fn zip_prefix(root: &str) -> String {
+	if root.is_empty() {
+		String::new()
+	} else {
+		format!("{}/", root)
+	}
+}
+
+// Reads a whole file from a zip, with `path` relative to `root`. `None` if it isn't in the zip,
+// an error if it is but can't be decompressed
+fn read_zip_file<R: Read + std::io::Seek>(
+	archive: &mut ZipArchive<R>,
+	root: &str,
+	path: &str,
+) -> std::io::Result<Option<Vec<u8>>> {
+	if let Ok(mut f) = archive.by_name(&zip_name(root, path)) {
+		let mut buf = Vec::new();
+		f.read_to_end(&mut buf)?;
+		Ok(Some(buf))
+	} else {
+		Ok(None)
+	}
+}
+
+// Adds the files inside `dir` to `files`, prefixed by `prefix`
+fn list_dir_recursive(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+	if let Ok(entries) = fs::read_dir(dir) {
+		for entry in entries.filter_map(|e| e.ok()) {
+			let name = entry.file_name().to_string_lossy().to_string();
+			let path = if prefix.is_empty() {
+				name
+			} else {
+				format!("{}/{}", prefix, name)
+			};
+			match entry.file_type() {
+				Ok(t) if t.is_dir() => list_dir_recursive(&entry.path(), &path, files),
+				Ok(_) => files.push(path),
+				Err(_) => (),
+			}
+		}
+	}
+}
+
+impl<'a> std::fmt::Debug for Layer<'a> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+		let kind = match &self.source {
+			LayerSource::Directory(dir) => format!("dir {:?}", dir),
+			LayerSource::Zip { archive, .. } => format!("zip ({} entries)", archive.len()),
+			LayerSource::Memory(files) => format!("memory ({} files)", files.len()),
+		};
+		write!(f, "{} [{}]: {}", self.name, self.priority, kind)
+	}
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::fs::metadata;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Checking the disk every frame is wasteful, a few times per second is enough for live editing
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Keeps track of the modification time of every file loaded from a directory layer
+#[derive(Debug)]
+pub struct Watcher {
+	files: HashMap<PathBuf, (PathBuf, Option<SystemTime>)>, // Asset path: (Disk path, Modified)
+	last_poll: Instant,
+}
+
+impl Watcher {
+	pub fn new() -> Self {
+		Self {
+			files: HashMap::new(),
+			last_poll: Instant::now(),
+		}
+	}
+
+	/// Starts watching an asset path, stored on disk at `disk_path`
+	pub fn watch<P: AsRef<Path>>(&mut self, p: P, disk_path: PathBuf) {
+		let p = p.as_ref().to_path_buf();
+		if self
+			.files
+			.get(&p)
+			.map(|(d, _)| d != &disk_path)
+			.unwrap_or(true)
+		{
+			let modified = Self::modified(&disk_path);
+			self.files.insert(p, (disk_path, modified));
+		}
+	}
+
+	#[allow(dead_code)]
+	pub fn unwatch<P: AsRef<Path>>(&mut self, p: P) {
+		self.files.remove(p.as_ref());
+	}
+
+	/// Returns the watched paths that changed since the last poll
+	pub fn poll(&mut self) -> Vec<PathBuf> {
+		let mut changed = Vec::new();
+		if self.last_poll.elapsed() < POLL_INTERVAL {
+			return changed;
+		}
+		self.last_poll = Instant::now();
+		for (p, (disk_path, last_modified)) in self.files.iter_mut() {
+			let modified = Self::modified(disk_path);
+			if modified != *last_modified {
+				*last_modified = modified;
+				changed.push(p.clone());
+			}
+		}
+		changed
+	}
+
+	fn modified(p: &Path) -> Option<SystemTime> {
+		metadata(p).and_then(|m| m.modified()).ok()
+	}
+}
@@ -10,17 +10,33 @@ use luminance_glfw::{
 
 use cgmath::{EuclideanSpace, InnerSpace, Point3, Quaternion, Rad, Rotation3, Vector3};
 
-use std::path::Path;
+use std::env;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::time::Instant;
 
 mod engine;
 pub mod terrain;
+mod tools;
 
 const X_DEFAULT_SIZE: u32 = 1000;
 const Y_DEFAULT_SIZE: u32 = 1000;
 
+// Threads used to load assets in the background
+const LOADER_THREADS: usize = 2;
+
+// Animation events kept in the log of the settings panel, the oldest ones are dropped
+const LOG_LINES: usize = 50;
+
+// Environment variable with the path of the asset pack, used if there's no `--pack` argument
+const PACK_ENV_VAR: &str = "LUMINANCE_TEST_PACK";
+
 fn main() {
+	// Asset tools don't need a window
+	if let Some(code) = tools::run(&env::args().skip(1).collect::<Vec<String>>()) {
+		exit(code);
+	}
+
 	//let t = terrain::generate(100,100);
 	// println!("{:?} {:?}", Vector3 {
 	// 	x: 0.,
@@ -42,8 +58,12 @@ fn main() {
 	let res = match surface {
 		Ok(surface) => {
 			eprintln!("graphics surface created");
-			main_loop(surface);
-			0
+			if let Err(e) = main_loop(surface, pack_path()) {
+				eprintln!("{}", e);
+				1
+			} else {
+				0
+			}
 		}
 
 		Err(e) => {
@@ -56,12 +76,29 @@ fn main() {
 	}
 }
 
-fn main_loop(mut surface: GlfwSurface) {
-	let mut file_loader = engine::FileLoader::new();
+// The asset pack chosen with `--pack <path>` or the LUMINANCE_TEST_PACK env var
+fn pack_path() -> Option<PathBuf> {
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		if arg == "--pack" {
+			return args.next().map(PathBuf::from);
+		}
+	}
+	env::var_os(PACK_ENV_VAR).map(PathBuf::from)
+}
+
+fn main_loop(mut surface: GlfwSurface, pack: Option<PathBuf>) -> engine::Result<()> {
+	let mut file_loader = if let Some(pack) = pack {
+		engine::FileLoader::from_pack(pack)
+	} else {
+		engine::FileLoader::new()
+	};
+	let mut assets = engine::AssetServer::new();
 	let mut size = [X_DEFAULT_SIZE, Y_DEFAULT_SIZE];
 	let start_t = Instant::now();
 
 	let entity = engine::hud::Entity::load(
+		&mut assets,
 		&mut file_loader,
 		&mut surface,
 		&[
@@ -72,8 +109,7 @@ fn main_loop(mut surface: GlfwSurface) {
 		],
 		&[0, 1, 2, 0, 2, 3],
 		Path::new("texture.ron"),
-	)
-	.expect("Error creeating entity");
+	)?;
 
 	let font = engine::text::Font::new(
 		"Roboto",
@@ -84,15 +120,28 @@ fn main_loop(mut surface: GlfwSurface) {
 	// let mut rt_font = engine::text_rusttype::Font::new("Roboto", engine::text_rusttype::FontWeight::Black, engine::text_rusttype::FontStyle::Regular, 20.);
 	// font.set_color(engine::RgbaColor::new(255,0,0,170));
 	let mut entity2 = engine::hud::Entity::new_entity_from_string(
+		&mut assets,
 		&mut file_loader,
 		&mut surface,
 		"Lies & deception".into(),
 		&font,
-	)
-	.unwrap();
-
-	entity2.set_pos([100, 100]);
-	entity2.set_depth(-1.0);
+	)?;
+
+	// Keeps its place in the top left corner and its size relative to the window when it's resized
+	entity2.set_layout(
+		engine::hud::Layout::new(engine::hud::Anchor::TopLeft)
+			.with_offset(
+				engine::hud::Offset::Pixels(-400),
+				engine::hud::Offset::Pixels(-100),
+			)
+			.with_reference_height(Y_DEFAULT_SIZE),
+	);
+	// Slides in from the left when the game starts
+	entity2.play_tween(
+		"Slide in",
+		engine::hud::Tween::to(engine::hud::Target::Offset([100, -100]), 0.8)
+			.ease(engine::hud::Easing::BackOut),
+	);
 
 	let mut back_buffer = surface.back_buffer().unwrap();
 	let depth_map_size = [1024, 1024];
@@ -124,38 +173,200 @@ fn main_loop(mut surface: GlfwSurface) {
 
 	let pos = [0, 0];
 
-	let mut hud_registry = engine::EntityRegistry::new();
-	//hud_registry.register(&"Playeer", entity);
-	hud_registry.register(&"Text", entity2);
-	// hud_registry.register(&"Shadow", depth_e);
+	let mut hud_scene = engine::hud::Scene::new();
+	//hud_scene.add(&"Playeer", entity);
+	hud_scene.add(&"Text", entity2);
+	// Follows the title, H hides both
+	let mut subtitle = engine::hud::Entity::new_entity_from_string(
+		&mut assets,
+		&mut file_loader,
+		&mut surface,
+		"(and more lies)".into(),
+		&font,
+	)?;
+	subtitle.set_layout(
+		engine::hud::Layout::new(engine::hud::Anchor::BottomLeft)
+			.with_pivot(engine::hud::Anchor::TopLeft),
+	);
+	subtitle.set_scale(0.75);
+	subtitle.play_tween(
+		"Pulse",
+		engine::hud::Tween::sequence(vec![
+			engine::hud::Tween::to(engine::hud::Target::Opacity(0.4), 0.6),
+			engine::hud::Tween::to(engine::hud::Target::Opacity(1.0), 0.6),
+		])
+		.ease(engine::hud::Easing::SineInOut)
+		.looped(),
+	);
+	hud_scene
+		.add_child(&"Text", &"Subtitle", subtitle)
+		.expect("The title was just added");
+	// hud_scene.add(&"Shadow", depth_e);
 
 	let renderer = engine::hud::Renderer::new();
+	// Draws the HUD and the widgets in a few calls
+	let mut batcher = engine::hud::Batcher::new();
+	// Frame times and what the renderers drew, F3 shows them
+	let mut perf_overlay = engine::hud::PerfOverlay::new(&mut surface)?;
+
+	// Settings in the top right corner, Tab frees the cursor to use them
+	let mut ui = engine::hud::Ui::new(&mut surface, engine::hud::Theme::default())?;
+	let top_left = engine::hud::Layout::new(engine::hud::Anchor::TopLeft);
+	let settings = vec![
+		ui.label(
+			&mut assets,
+			&mut file_loader,
+			&mut surface,
+			&"Title",
+			&"Settings (Tab)",
+			top_left,
+		)?,
+		ui.slider(&mut surface, &"Speed", 0.5, [220, 20], top_left)?,
+		ui.checkbox(
+			&mut assets,
+			&mut file_loader,
+			&mut surface,
+			&"Shadows",
+			&"Show shadow map",
+			true,
+			top_left,
+		)?,
+		ui.progress_bar(&mut surface, &"Time", 0.0, [220, 8], top_left)?,
+		// The animation events, the mouse wheel scrolls them
+		ui.scroll_view(&mut surface, &"Log", [220, 90], top_left, Vec::new())?,
+		ui.button(
+			&mut assets,
+			&mut file_loader,
+			&mut surface,
+			&"Quit",
+			&"Quit",
+			[220, 32],
+			top_left,
+		)?,
+	];
+	let settings = ui.column(
+		&mut surface,
+		&"Settings",
+		engine::hud::Layout::new(engine::hud::Anchor::TopRight).with_offset(
+			engine::hud::Offset::Pixels(-10),
+			engine::hud::Offset::Pixels(-10),
+		),
+		settings,
+	)?;
+	ui.add(settings);
+	let mut ui_mode = false;
+	let mut camera_speed = 0.5;
+	let mut show_shadows = true;
+	let mut log_count = 0;
 
 	let mut key_registry = engine::KeyRegistry::new();
 
-	let mut spatial_renderer =
-		engine::spatial::Renderer::new(&mut file_loader, &mut surface, size, depth_map_size);
+	// Generate the terrain & parse the models in the background, while showing the progress
+	let loader = engine::loading::Loader::new(LOADER_THREADS);
+	let mut pending_meshes = engine::spatial::Renderer::load_meshes(&loader, &mut file_loader)?;
+	let mut loading_registry = engine::EntityRegistry::new();
+	let mut loading_text = engine::hud::Entity::new_entity_from_string(
+		&mut assets,
+		&mut file_loader,
+		&mut surface,
+		"Loading 0%".into(),
+		&font,
+	)?;
+	loading_text.set_layout(engine::hud::Layout::new(engine::hud::Anchor::Center));
+	loading_registry.register(&"Loading", loading_text);
+	let mut last_progress = 0;
+	while !pending_meshes.is_ready() {
+		let mut resized = false;
+		for event in surface.poll_events() {
+			match event {
+				WindowEvent::Close | WindowEvent::Key(Key::Escape, _, Action::Release, _) => {
+					return Ok(())
+				}
+				WindowEvent::FramebufferSize(x, y) => {
+					size = [x as u32, y as u32];
+					resized = true;
+				}
+				_ => (),
+			}
+		}
+		if resized {
+			back_buffer = surface.back_buffer().unwrap();
+		}
+
+		let progress = (loader.progress() * 100.) as u32;
+		if progress != last_progress {
+			last_progress = progress;
+			loading_registry.get_mut(&"Loading").unwrap().update_text(
+				&mut assets,
+				&mut file_loader,
+				&mut surface,
+				&format!("Loading {}%", progress),
+				&font,
+			)?;
+		}
+
+		surface.pipeline_builder().pipeline(
+			&back_buffer,
+			&PipelineState::default().set_clear_color([0., 0., 0., 1.]),
+			|pipeline, mut shd_gate| {
+				renderer.render(&loading_registry, &mut shd_gate, &pipeline, &size, None);
+			},
+		);
+		surface.swap_buffers();
+	}
+
+	let mut spatial_renderer = engine::spatial::Renderer::from_meshes(
+		&mut assets,
+		&mut surface,
+		size,
+		depth_map_size,
+		pending_meshes,
+	)?;
 	// let mut depth_renderer = engine::spatial::depth::Renderer::new(&mut file_loader, &mut surface, size);
 
 	let mut last_pos = [0.0; 2];
 	let mut f = true;
+	let mut frame_timer = engine::FrameTimer::new();
+	let mut render_stats = [("World", engine::RenderStats::default()); 3];
 
 	'app: loop {
 		let mut resized = false;
+		let mut toggle_ui = false;
 		// handle events
 		for event in surface.poll_events() {
 			key_registry.event(&event);
+			if ui_mode {
+				for ui_event in ui.event(&event) {
+					match ui_event {
+						engine::hud::UiEvent::Clicked(name) if name == "Quit" => break 'app,
+						engine::hud::UiEvent::Toggled(name, checked) if name == "Shadows" => {
+							show_shadows = checked
+						}
+						engine::hud::UiEvent::Changed(name, value) if name == "Speed" => {
+							camera_speed = value
+						}
+						_ => (),
+					}
+				}
+			}
 			match &event {
 				WindowEvent::Close | WindowEvent::Key(Key::Escape, _, Action::Release, _) => {
 					break 'app
 				}
+				WindowEvent::Key(Key::Tab, _, Action::Press, _) => toggle_ui = true,
+				WindowEvent::Key(Key::H, _, Action::Press, _) => {
+					let title = hud_scene.get_mut(&"Text").unwrap();
+					title.set_visible(!title.is_visible());
+				}
+				WindowEvent::Key(Key::F3, _, Action::Press, _) => perf_overlay.toggle(),
 				WindowEvent::Key(Key::K, _, Action::Press, _) => {
-					// hud_registry
+					// hud_scene
 					// 	.get_mut(&"Playeer")
 					// 	.unwrap()
 					// 	.set_state("2")
 					// 	.expect("Error setting state");
 				}
+				WindowEvent::CursorPos(_, _) if ui_mode => (),
 				WindowEvent::CursorPos(x, y) => {
 					if !f {
 						let move_x = -(*x - last_pos[0]);
@@ -190,10 +401,73 @@ fn main_loop(mut surface: GlfwSurface) {
 			}
 		}
 
+		if toggle_ui {
+			ui_mode = !ui_mode;
+			surface.window.set_cursor_mode(if ui_mode {
+				CursorMode::Normal
+			} else {
+				CursorMode::Disabled
+			});
+			// The cursor jumps when it's captured again
+			f = true;
+		}
+
+		// reload the assets that changed on disk
+		for changed in file_loader.changed_files() {
+			assets.invalidate(&changed);
+			if changed.starts_with(&*engine::MODELS_PATH) {
+				if let Err(e) =
+					spatial_renderer.reload_mesh(&mut assets, &mut file_loader, &mut surface)
+				{
+					eprintln!("Error reloading mesh: {}", e);
+				}
+			}
+			for e in hud_scene.values_mut() {
+				if e.uses_file(&changed) {
+					if let Err(err) = e.reload(&mut assets, &mut file_loader, &mut surface) {
+						eprintln!("Error reloading entity: {}", err);
+					}
+				}
+			}
+			if let Err(e) = ui.reload(&mut assets, &mut file_loader, &mut surface, &changed) {
+				eprintln!("Error reloading the UI: {}", e);
+			}
+			if let Err(e) =
+				perf_overlay.reload(&mut assets, &mut file_loader, &mut surface, &changed)
+			{
+				eprintln!("Error reloading the performance overlay: {}", e);
+			}
+		}
+
+		// advance the HUD animations
+		let dt = frame_timer.tick();
+		for e in hud_scene.values_mut() {
+			for event in e.update_animation(dt) {
+				// Events can repeat, the names of the lines can't
+				let name = format!("Log {}", log_count);
+				log_count += 1;
+				let line = ui.label(
+					&mut assets,
+					&mut file_loader,
+					&mut surface,
+					&name,
+					&format!("{:?}", event),
+					top_left,
+				)?;
+				if ui.add_child("Log", line).is_ok() {
+					if ui.get("Log").unwrap().children().len() > LOG_LINES {
+						ui.remove_child("Log", 0)?;
+					}
+					let log = ui.get_mut("Log").unwrap();
+					let _ = log.set_scroll(u32::MAX);
+				}
+			}
+		}
+
 		key_registry.for_pressed_keys(|key| {
 			let mut fd_scale = 0.0;
 			let mut rt_scale = 0.0;
-			let speed = 0.5;
+			let speed = camera_speed;
 			match key {
 				Key::W => {
 					//pos[1] += 10;
@@ -234,7 +508,7 @@ fn main_loop(mut surface: GlfwSurface) {
 
 		// println!("{:?}", d.size());
 		// entity.set_pos(pos.clone());
-		// hud_registry
+		// hud_scene
 		// 	.get_mut(&"Playeer")
 		// 	.unwrap()
 		// 	.set_pos(pos.clone());
@@ -242,8 +516,26 @@ fn main_loop(mut surface: GlfwSurface) {
 		// rendering code goes here
 		let t = start_t.elapsed().as_millis() as f32 * 1e-3;
 
-		// hud_registry.get_mut(&"Text").unwrap().update_text(&mut surface, &format!("{:.2}", t), &font).expect("Error updating text"); // Dynamic text rendering
+		// hud_scene.get_mut(&"Text").unwrap().update_text(&mut assets, &mut file_loader, &mut surface, &format!("{:.2}", t), &font).expect("Error updating text"); // Dynamic text rendering
 		let color = [t.cos(), t.sin(), 0.5, 1.];
+		if let Some(time) = ui.get_mut("Time") {
+			let _ = time.set_value((t / 10.).fract());
+		}
+		ui.layout(size);
+		hud_scene.update(size);
+		let mut hud_entities = hud_scene.values();
+		hud_entities.extend(ui.entities());
+		let asset_stats = assets.stats();
+		perf_overlay.update(
+			&mut assets,
+			&mut file_loader,
+			&mut surface,
+			&frame_timer,
+			&render_stats,
+			asset_stats,
+		)?;
+		hud_entities.extend(perf_overlay.entities());
+		batcher.prepare(&mut surface, hud_entities, size)?;
 
 		// draw the shadows
 		surface.pipeline_builder().pipeline(
@@ -261,19 +553,26 @@ fn main_loop(mut surface: GlfwSurface) {
 			|pipeline, mut shd_gate| {
 				spatial_renderer.render(&mut shd_gate, &pipeline, &size);
 				// Render the HUD last
-				renderer.render(
-					&hud_registry,
-					&mut shd_gate,
-					&pipeline,
-					&size,
-					&depth_e,
-					depth_fb.depth_slot(),
-				);
+				if show_shadows {
+					renderer.render_entities(
+						Vec::new(),
+						&mut shd_gate,
+						&pipeline,
+						&size,
+						Some((&depth_e, depth_fb.depth_slot())),
+					);
+				}
+				batcher.render(&mut shd_gate, &pipeline);
 			},
 		);
+		render_stats = [
+			("World", spatial_renderer.take_stats()),
+			("HUD", renderer.take_stats()),
+			("HUD batches", batcher.take_stats()),
+		];
 
 		// let tex = engine::depth_texture_to_color(&mut surface, );
-		// hud_registry
+		// hud_scene
 		// 	.get_mut(&"Shadow")
 		// 	.unwrap()
 		// 	.update_tex(*(depth_fb.depth_slot().clone()));
@@ -281,4 +580,5 @@ fn main_loop(mut surface: GlfwSurface) {
 		// swap buffer chains
 		surface.swap_buffers();
 	}
+	Ok(())
 }
@@ -0,0 +1,100 @@
+// Command line tools for the assets, run with `cargo run -- <tool> <args>`
+
+use std::path::Path;
+
+use crate::engine;
+
+// Empty pixels between the sprites of an atlas
+const ATLAS_PADDING: u32 = 1;
+
+/// Runs the tool named by the first argument, returns the exit code or `None` if it isn't a tool
+pub fn run(args: &[String]) -> Option<i32> {
+	let (res, usage) = match args.first().map(|s| s.as_str()) {
+		Some("atlas") => (atlas(&args[1..]), ATLAS_USAGE),
+		Some("validate") => (validate(&args[1..]), VALIDATE_USAGE),
+		_ => return None,
+	};
+	Some(match res {
+		Some(Ok(())) => 0,
+		Some(Err(e)) => {
+			eprintln!("{}", e);
+			1
+		}
+		None => {
+			eprintln!("Usage: {}", usage);
+			2
+		}
+	})
+}
+
+const ATLAS_USAGE: &str = "atlas <sprites dir> <atlas png> [padding]";
+
+// Writes the atlas and its TextureData RON (same name, .ron extension) next to it.
+// `None` if the arguments are wrong
+fn atlas(args: &[String]) -> Option<engine::Result<()>> {
+	if args.len() < 2 || args.len() > 3 {
+		return None;
+	}
+	Some(write_atlas(&args[0], &args[1], args.get(2)))
+}
+
+fn write_atlas(dir: &str, image_path: &str, padding: Option<&String>) -> engine::Result<()> {
+	let image_path = Path::new(image_path);
+	let padding = match padding {
+		Some(p) => p
+			.parse()
+			.map_err(|_| engine::Error::invalid(p, "the padding must be a number"))?,
+		None => ATLAS_PADDING,
+	};
+	let file = image_path
+		.file_name()
+		.map(|f| f.to_string_lossy().to_string())
+		.ok_or_else(|| engine::Error::invalid(image_path, "not a file name"))?;
+	let atlas = engine::atlas::Atlas::pack_dir(dir, file, padding)?;
+	let ron_path = image_path.with_extension("ron");
+	atlas.save(image_path, &ron_path)?;
+	println!(
+		"Packed {} sprites in a {}x{} atlas: {:?}, {:?}",
+		atlas.data.uv.len(),
+		atlas.image.width(),
+		atlas.image.height(),
+		image_path,
+		ron_path
+	);
+	Ok(())
+}
+
+const VALIDATE_USAGE: &str = "validate [pack]";
+
+// Checks the assets dir, or only the pack if there's one, and prints every problem found
+fn validate(args: &[String]) -> Option<engine::Result<()>> {
+	if args.len() > 1 {
+		return None;
+	}
+	Some(validate_assets(args.first()))
+}
+
+fn validate_assets(pack: Option<&String>) -> engine::Result<()> {
+	let mut file_loader = match pack {
+		Some(pack) => engine::FileLoader::pack_only(pack)?,
+		None => engine::FileLoader::new(),
+	};
+	let report = engine::validate::validate(&mut file_loader);
+	for e in &report.errors {
+		eprintln!("{}", e);
+	}
+	println!(
+		"Checked {} assets, {} problems found",
+		report.checked,
+		report.errors.len()
+	);
+	if report.is_ok() {
+		Ok(())
+	} else {
+		// The problems were already printed, this only sets the exit code
+		Err(engine::Error::invalid(
+			pack.map(|p| p.as_str()).unwrap_or("assets"),
+			"validation failed",
+		))
+	}
+}